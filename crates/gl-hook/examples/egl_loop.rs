@@ -0,0 +1,146 @@
+//! Minimal EGL application that swaps a surfaceless pbuffer, for exercising
+//! the hook without a display or GPU:
+//!
+//! ```sh
+//! cargo build -p rs_overlay_gl_hook --example egl_loop
+//! LIBGL_ALWAYS_SOFTWARE=1 \
+//! LD_PRELOAD=target/debug/librs_overlay_gl_hook.so \
+//!     target/debug/examples/egl_loop 600
+//! ```
+//!
+//! libEGL is opened with `dlopen` and its functions looked up with `dlsym`,
+//! the way most engines load GL, so this also covers the `dlsym` wrapper.
+
+use std::{
+    ffi::{CStr, c_int, c_uint, c_void},
+    mem, ptr, thread,
+    time::{Duration, Instant},
+};
+
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+const EGL_PLATFORM_SURFACELESS_MESA: c_uint = 0x31dd;
+const EGL_NONE: c_int = 0x3038;
+const EGL_SURFACE_TYPE: c_int = 0x3033;
+const EGL_PBUFFER_BIT: c_int = 0x0001;
+const EGL_RENDERABLE_TYPE: c_int = 0x3040;
+const EGL_OPENGL_ES2_BIT: c_int = 0x0004;
+const EGL_WIDTH: c_int = 0x3057;
+const EGL_HEIGHT: c_int = 0x3056;
+const EGL_CONTEXT_CLIENT_VERSION: c_int = 0x3098;
+const EGL_OPENGL_ES_API: c_uint = 0x30a0;
+const GL_COLOR_BUFFER_BIT: c_uint = 0x4000;
+
+type Display = *mut c_void;
+type Config = *mut c_void;
+type Surface = *mut c_void;
+type Context = *mut c_void;
+
+struct Egl {
+    get_platform_display: unsafe extern "C" fn(c_uint, *mut c_void, *const isize) -> Display,
+    initialize: unsafe extern "C" fn(Display, *mut c_int, *mut c_int) -> c_uint,
+    bind_api: unsafe extern "C" fn(c_uint) -> c_uint,
+    choose_config:
+        unsafe extern "C" fn(Display, *const c_int, *mut Config, c_int, *mut c_int) -> c_uint,
+    create_pbuffer_surface: unsafe extern "C" fn(Display, Config, *const c_int) -> Surface,
+    create_context: unsafe extern "C" fn(Display, Config, Context, *const c_int) -> Context,
+    make_current: unsafe extern "C" fn(Display, Surface, Surface, Context) -> c_uint,
+    swap_buffers: unsafe extern "C" fn(Display, Surface) -> c_uint,
+    get_proc_address: unsafe extern "C" fn(*const i8) -> *mut c_void,
+    terminate: unsafe extern "C" fn(Display) -> c_uint,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let frames: u64 = match std::env::args().nth(1) {
+        Some(count) => count.parse()?,
+        None => 600,
+    };
+
+    unsafe {
+        let library = libc::dlopen(c"libEGL.so.1".as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if library.is_null() {
+            return Err("libEGL.so.1 not found".into());
+        }
+        let egl = Egl {
+            get_platform_display: symbol(library, c"eglGetPlatformDisplay")?,
+            initialize: symbol(library, c"eglInitialize")?,
+            bind_api: symbol(library, c"eglBindAPI")?,
+            choose_config: symbol(library, c"eglChooseConfig")?,
+            create_pbuffer_surface: symbol(library, c"eglCreatePbufferSurface")?,
+            create_context: symbol(library, c"eglCreateContext")?,
+            make_current: symbol(library, c"eglMakeCurrent")?,
+            swap_buffers: symbol(library, c"eglSwapBuffers")?,
+            get_proc_address: symbol(library, c"eglGetProcAddress")?,
+            terminate: symbol(library, c"eglTerminate")?,
+        };
+
+        let display =
+            (egl.get_platform_display)(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+        if display.is_null() || (egl.initialize)(display, ptr::null_mut(), ptr::null_mut()) == 0 {
+            return Err("could not initialize a surfaceless EGL display".into());
+        }
+        (egl.bind_api)(EGL_OPENGL_ES_API);
+
+        let config_attribs = [
+            EGL_SURFACE_TYPE,
+            EGL_PBUFFER_BIT,
+            EGL_RENDERABLE_TYPE,
+            EGL_OPENGL_ES2_BIT,
+            EGL_NONE,
+        ];
+        let mut config = ptr::null_mut();
+        let mut count = 0;
+        if (egl.choose_config)(display, config_attribs.as_ptr(), &mut config, 1, &mut count) == 0
+            || count == 0
+        {
+            return Err("no pbuffer-capable EGL config".into());
+        }
+        let surface_attribs = [EGL_WIDTH, 640, EGL_HEIGHT, 480, EGL_NONE];
+        let surface = (egl.create_pbuffer_surface)(display, config, surface_attribs.as_ptr());
+        let context_attribs = [EGL_CONTEXT_CLIENT_VERSION, 2, EGL_NONE];
+        let context =
+            (egl.create_context)(display, config, ptr::null_mut(), context_attribs.as_ptr());
+        if surface.is_null()
+            || context.is_null()
+            || (egl.make_current)(display, surface, surface, context) == 0
+        {
+            return Err("could not create a pbuffer context".into());
+        }
+
+        let clear_color: unsafe extern "C" fn(f32, f32, f32, f32) =
+            proc_address(&egl, c"glClearColor")?;
+        let clear: unsafe extern "C" fn(c_uint) = proc_address(&egl, c"glClear")?;
+        println!("swapping {frames} frames");
+
+        for frame in 0..frames {
+            let started = Instant::now();
+            let shade = (frame % 120) as f32 / 120.0;
+            clear_color(shade, 0.2, 1.0 - shade, 1.0);
+            clear(GL_COLOR_BUFFER_BIT);
+            (egl.swap_buffers)(display, surface);
+
+            if let Some(remaining) = FRAME_TIME.checked_sub(started.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        (egl.terminate)(display);
+    }
+    Ok(())
+}
+
+unsafe fn symbol<F: Copy>(library: *mut c_void, name: &CStr) -> Result<F, String> {
+    let function = unsafe { libc::dlsym(library, name.as_ptr()) };
+    if function.is_null() {
+        return Err(format!("{} not found", name.to_string_lossy()));
+    }
+    Ok(unsafe { mem::transmute_copy(&function) })
+}
+
+unsafe fn proc_address<F: Copy>(egl: &Egl, name: &CStr) -> Result<F, String> {
+    let function = unsafe { (egl.get_proc_address)(name.as_ptr()) };
+    if function.is_null() {
+        return Err(format!("{} not found", name.to_string_lossy()));
+    }
+    Ok(unsafe { mem::transmute_copy(&function) })
+}
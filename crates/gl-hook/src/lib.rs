@@ -0,0 +1,284 @@
+//! Preloadable library that timestamps every `glXSwapBuffers` and
+//! `eglSwapBuffers` and publishes the timestamps to rs_overlay through
+//! `rs_overlay_shm`:
+//!
+//! ```sh
+//! LD_PRELOAD=/path/to/librs_overlay_gl_hook.so game
+//! ```
+//!
+//! Applications that load GL at run time find the swap functions with
+//! `dlsym`, `glXGetProcAddress` or `eglGetProcAddress`, so those are wrapped
+//! as well and hand out the hooked swap functions instead of the real ones.
+
+use std::{
+    cell::Cell,
+    ffi::{CStr, c_char, c_uint, c_ulong, c_void},
+    mem,
+    ptr::null_mut,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicPtr, Ordering},
+    },
+};
+
+use rs_overlay_shm::{Api, Publisher};
+
+type DlsymFn = unsafe extern "C" fn(*mut c_void, *const c_char) -> *mut c_void;
+type GlxSwapBuffersFn = unsafe extern "C" fn(*mut c_void, c_ulong);
+type EglSwapBuffersFn = unsafe extern "C" fn(*mut c_void, *mut c_void) -> c_uint;
+type GetProcAddressFn = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+
+const GLX_LIBRARIES: &[&CStr] = &[c"libGLX.so.0", c"libGL.so.1"];
+const EGL_LIBRARIES: &[&CStr] = &[c"libEGL.so.1"];
+
+static GLX_SWAP_BUFFERS: RealFn = RealFn::new(c"glXSwapBuffers", GLX_LIBRARIES);
+static EGL_SWAP_BUFFERS: RealFn = RealFn::new(c"eglSwapBuffers", EGL_LIBRARIES);
+static GLX_GET_PROC_ADDRESS: RealFn = RealFn::new(c"glXGetProcAddress", GLX_LIBRARIES);
+static GLX_GET_PROC_ADDRESS_ARB: RealFn = RealFn::new(c"glXGetProcAddressARB", GLX_LIBRARIES);
+static EGL_GET_PROC_ADDRESS: RealFn = RealFn::new(c"eglGetProcAddress", EGL_LIBRARIES);
+
+static PUBLISHER: Mutex<PublisherState> = Mutex::new(PublisherState::Unopened);
+
+enum PublisherState {
+    Unopened,
+    Open(Publisher),
+    /// Creating the channel failed; not retried on every swap.
+    Failed,
+}
+
+thread_local! {
+    /// Hooked swaps currently running on this thread, so a swap that calls
+    /// another hooked swap internally is counted once.
+    static SWAP_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// The function a wrapper forwards to.
+struct RealFn {
+    name: &'static CStr,
+    /// Already-loaded libraries to look in when the function is not in the
+    /// global scope.
+    libraries: &'static [&'static CStr],
+    function: AtomicPtr<c_void>,
+}
+
+impl RealFn {
+    const fn new(name: &'static CStr, libraries: &'static [&'static CStr]) -> Self {
+        Self {
+            name,
+            libraries,
+            function: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    /// Remembers `function`, found through an intercepted lookup, unless the
+    /// real function is already known. Covers libraries the application
+    /// opened with `RTLD_LOCAL`, which `RTLD_NEXT` cannot see.
+    fn offer(&self, function: *mut c_void) {
+        let _ = self.function.compare_exchange(
+            null_mut(),
+            function,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+    }
+
+    /// Null when no loaded library provides the function.
+    fn get(&self) -> *mut c_void {
+        let known = self.function.load(Ordering::Acquire);
+        if !known.is_null() {
+            return known;
+        }
+        let Some(real_dlsym) = real_dlsym() else {
+            return null_mut();
+        };
+        let mut found = unsafe { real_dlsym(libc::RTLD_NEXT, self.name.as_ptr()) };
+        for library in self.libraries {
+            if !found.is_null() {
+                break;
+            }
+            let handle =
+                unsafe { libc::dlopen(library.as_ptr(), libc::RTLD_LAZY | libc::RTLD_NOLOAD) };
+            if !handle.is_null() {
+                found = unsafe { real_dlsym(handle, self.name.as_ptr()) };
+                unsafe { libc::dlclose(handle) };
+            }
+        }
+        if !found.is_null() {
+            self.offer(found);
+        }
+        self.function.load(Ordering::Acquire)
+    }
+}
+
+/// glibc's `dlsym`, which this library replaces.
+fn real_dlsym() -> Option<DlsymFn> {
+    static REAL: OnceLock<Option<DlsymFn>> = OnceLock::new();
+    *REAL.get_or_init(|| {
+        // Looking up `dlsym` with `dlsym` would find this library's own.
+        [c"GLIBC_2.34", c"GLIBC_2.17", c"GLIBC_2.2.5"]
+            .iter()
+            .find_map(|version| {
+                let function =
+                    unsafe { libc::dlvsym(libc::RTLD_NEXT, c"dlsym".as_ptr(), version.as_ptr()) };
+                (!function.is_null())
+                    .then(|| unsafe { mem::transmute::<*mut c_void, DlsymFn>(function) })
+            })
+    })
+}
+
+/// The real function slot and wrapper for each hooked symbol name.
+fn hooked(name: &CStr) -> Option<(&'static RealFn, *mut c_void)> {
+    Some(match name.to_bytes() {
+        b"glXSwapBuffers" => (
+            &GLX_SWAP_BUFFERS,
+            glXSwapBuffers as GlxSwapBuffersFn as *mut c_void,
+        ),
+        b"eglSwapBuffers" => (
+            &EGL_SWAP_BUFFERS,
+            eglSwapBuffers as EglSwapBuffersFn as *mut c_void,
+        ),
+        b"glXGetProcAddress" => (
+            &GLX_GET_PROC_ADDRESS,
+            glXGetProcAddress as GetProcAddressFn as *mut c_void,
+        ),
+        b"glXGetProcAddressARB" => (
+            &GLX_GET_PROC_ADDRESS_ARB,
+            glXGetProcAddressARB as GetProcAddressFn as *mut c_void,
+        ),
+        b"eglGetProcAddress" => (
+            &EGL_GET_PROC_ADDRESS,
+            eglGetProcAddress as GetProcAddressFn as *mut c_void,
+        ),
+        _ => return None,
+    })
+}
+
+/// Hands out the wrapper in place of `function` when `name` is hooked.
+fn substitute(name: &CStr, function: *mut c_void) -> *mut c_void {
+    let Some((real, wrapper)) = hooked(name) else {
+        return function;
+    };
+    // A global-scope lookup finds the wrapper itself, which must never be
+    // forwarded to.
+    if function != wrapper {
+        real.offer(function);
+    }
+    wrapper
+}
+
+/// Replaces glibc's `dlsym` so run-time lookups of the swap functions get the
+/// wrappers.
+///
+/// `RTLD_NEXT` lookups are resolved relative to this library rather than
+/// the caller, which only matters for callers that are themselves preloaded.
+///
+/// # Safety
+///
+/// Same contract as `dlsym`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void {
+    let Some(real_dlsym) = real_dlsym() else {
+        return null_mut();
+    };
+    let function = unsafe { real_dlsym(handle, symbol) };
+    if function.is_null() || symbol.is_null() {
+        return function;
+    }
+    substitute(unsafe { CStr::from_ptr(symbol) }, function)
+}
+
+/// # Safety
+///
+/// Same contract as the real `glXSwapBuffers`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn glXSwapBuffers(display: *mut c_void, drawable: c_ulong) {
+    let real = GLX_SWAP_BUFFERS.get();
+    if real.is_null() {
+        return;
+    }
+    let real = unsafe { mem::transmute::<*mut c_void, GlxSwapBuffersFn>(real) };
+    timed(|| unsafe { real(display, drawable) })
+}
+
+/// # Safety
+///
+/// Same contract as the real `eglSwapBuffers`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eglSwapBuffers(display: *mut c_void, surface: *mut c_void) -> c_uint {
+    let real = EGL_SWAP_BUFFERS.get();
+    if real.is_null() {
+        return 0;
+    }
+    let real = unsafe { mem::transmute::<*mut c_void, EglSwapBuffersFn>(real) };
+    timed(|| unsafe { real(display, surface) })
+}
+
+/// # Safety
+///
+/// Same contract as the real `glXGetProcAddress`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn glXGetProcAddress(name: *const c_char) -> *mut c_void {
+    unsafe { get_proc_address(&GLX_GET_PROC_ADDRESS, name) }
+}
+
+/// # Safety
+///
+/// Same contract as the real `glXGetProcAddressARB`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn glXGetProcAddressARB(name: *const c_char) -> *mut c_void {
+    unsafe { get_proc_address(&GLX_GET_PROC_ADDRESS_ARB, name) }
+}
+
+/// # Safety
+///
+/// Same contract as the real `eglGetProcAddress`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eglGetProcAddress(name: *const c_char) -> *mut c_void {
+    unsafe { get_proc_address(&EGL_GET_PROC_ADDRESS, name) }
+}
+
+unsafe fn get_proc_address(real: &RealFn, name: *const c_char) -> *mut c_void {
+    let real = real.get();
+    if real.is_null() {
+        return null_mut();
+    }
+    let real = unsafe { mem::transmute::<*mut c_void, GetProcAddressFn>(real) };
+    let function = unsafe { real(name) };
+    if function.is_null() || name.is_null() {
+        return function;
+    }
+    substitute(unsafe { CStr::from_ptr(name) }, function)
+}
+
+/// Runs `swap` and publishes a timestamp once it returns.
+fn timed<R>(swap: impl FnOnce() -> R) -> R {
+    let outermost = SWAP_DEPTH.with(|depth| {
+        depth.set(depth.get() + 1);
+        depth.get() == 1
+    });
+    let result = swap();
+    SWAP_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    if outermost {
+        publish_swap();
+    }
+    result
+}
+
+fn publish_swap() {
+    let mut state = PUBLISHER.lock().unwrap();
+    if let PublisherState::Unopened = *state {
+        *state = match Publisher::create(Api::Gl, &app_name()) {
+            Ok(publisher) => PublisherState::Open(publisher),
+            Err(_) => PublisherState::Failed,
+        };
+    }
+    if let PublisherState::Open(publisher) = &*state {
+        publisher.publish_now();
+    }
+}
+
+fn app_name() -> String {
+    std::fs::read_to_string("/proc/self/comm")
+        .map(|comm| comm.trim_end().to_owned())
+        .unwrap_or_default()
+}
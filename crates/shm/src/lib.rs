@@ -0,0 +1,310 @@
+//! Shared-memory channel that in-process hooks use to hand frame timestamps
+//! to rs_overlay.
+//!
+//! Each hooked process creates one file in `/dev/shm` holding a fixed header
+//! and a ring of `CLOCK_MONOTONIC` present timestamps. The writer never
+//! blocks; readers keep their own cursor and skip ahead when they fall more
+//! than a ring behind.
+
+use std::{
+    ffi::CString,
+    fs, io,
+    os::unix::ffi::OsStrExt as _,
+    path::{Path, PathBuf},
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub const SHM_DIR: &str = "/dev/shm";
+const FILE_PREFIX: &str = "rs_overlay-frames.";
+const MAGIC: u32 = u32::from_le_bytes(*b"RSOF");
+const VERSION: u32 = 1;
+pub const CAPACITY: usize = 1024;
+const NAME_LEN: usize = 64;
+
+/// Graphics API the publishing hook intercepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Api {
+    Vulkan = 1,
+    Gl = 2,
+}
+
+impl Api {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Vulkan => "vulkan",
+            Self::Gl => "gl",
+        }
+    }
+
+    fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Vulkan),
+            2 => Some(Self::Gl),
+            _ => None,
+        }
+    }
+}
+
+#[repr(C)]
+struct Header {
+    magic: u32,
+    version: u32,
+    pid: u32,
+    api: u32,
+    app_name: [u8; NAME_LEN],
+    /// Number of timestamps ever written; slot `n % CAPACITY` holds the n-th.
+    write_count: AtomicU64,
+    timestamps: [AtomicU64; CAPACITY],
+}
+
+const MAP_LEN: usize = std::mem::size_of::<Header>();
+
+/// `CLOCK_MONOTONIC` in nanoseconds, the clock every timestamp is taken on.
+pub fn monotonic_ns() -> u64 {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now);
+    }
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+}
+
+struct Mapping {
+    header: *mut Header,
+}
+
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn open(path: &Path, create: bool) -> io::Result<Self> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        // The path is predictable, so whatever is there, a symlink planted
+        // by another user included, is replaced rather than followed.
+        let flags = if create {
+            if unsafe { libc::unlink(c_path.as_ptr()) } != 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(err);
+                }
+            }
+            libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC
+        } else {
+            libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_CLOEXEC
+        };
+        let fd = unsafe { libc::open(c_path.as_ptr(), flags, 0o600) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = Self::map(fd, create);
+        unsafe {
+            libc::close(fd);
+        }
+        result
+    }
+
+    fn map(fd: libc::c_int, create: bool) -> io::Result<Self> {
+        if create {
+            if unsafe { libc::ftruncate(fd, MAP_LEN as libc::off_t) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        } else {
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if (stat.st_size as usize) < MAP_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "frame channel is truncated",
+                ));
+            }
+        }
+        let prot = if create {
+            libc::PROT_READ | libc::PROT_WRITE
+        } else {
+            libc::PROT_READ
+        };
+        let address =
+            unsafe { libc::mmap(ptr::null_mut(), MAP_LEN, prot, libc::MAP_SHARED, fd, 0) };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            header: address.cast(),
+        })
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*self.header }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.header.cast(), MAP_LEN);
+        }
+    }
+}
+
+/// Writing end, owned by the hooked process.
+pub struct Publisher {
+    mapping: Mapping,
+    path: PathBuf,
+}
+
+impl Publisher {
+    pub fn create(api: Api, app_name: &str) -> io::Result<Self> {
+        let pid = std::process::id();
+        let path = Path::new(SHM_DIR).join(format!("{FILE_PREFIX}{pid}.{}", api.name()));
+        let mapping = Mapping::open(&path, true)?;
+        let header = unsafe { &mut *mapping.header };
+        header.pid = pid;
+        header.api = api as u32;
+        let name = app_name.as_bytes();
+        let len = name.len().min(NAME_LEN - 1);
+        header.app_name[..len].copy_from_slice(&name[..len]);
+        header.version = VERSION;
+        // Written last so readers never see a half-initialised header.
+        std::sync::atomic::fence(Ordering::Release);
+        header.magic = MAGIC;
+        Ok(Self { mapping, path })
+    }
+
+    pub fn publish(&self, timestamp_ns: u64) {
+        let header = self.mapping.header();
+        let index = header.write_count.load(Ordering::Relaxed);
+        header.timestamps[index as usize % CAPACITY].store(timestamp_ns, Ordering::Relaxed);
+        header.write_count.store(index + 1, Ordering::Release);
+    }
+
+    pub fn publish_now(&self) {
+        self.publish(monotonic_ns());
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A publisher found in [`SHM_DIR`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublisherInfo {
+    pub path: PathBuf,
+    pub pid: u32,
+    pub api: Api,
+    pub app_name: String,
+}
+
+impl PublisherInfo {
+    pub fn is_alive(&self) -> bool {
+        process_alive(self.pid)
+    }
+}
+
+fn process_alive(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Publishers whose process is still running. Channels left behind by
+/// processes that died without cleaning up are removed on the way.
+pub fn discover() -> Vec<PublisherInfo> {
+    let Ok(entries) = fs::read_dir(SHM_DIR) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        if !entry
+            .file_name()
+            .as_bytes()
+            .starts_with(FILE_PREFIX.as_bytes())
+        {
+            continue;
+        }
+        let Ok(subscriber) = Subscriber::open(&entry.path()) else {
+            continue;
+        };
+        if subscriber.info.is_alive() {
+            found.push(subscriber.info);
+        } else {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    found.sort_by_key(|info| info.pid);
+    found
+}
+
+/// Reading end, used by the overlay.
+pub struct Subscriber {
+    mapping: Mapping,
+    info: PublisherInfo,
+    read_count: u64,
+}
+
+impl Subscriber {
+    /// Starts reading at the newest timestamp; earlier ones are skipped.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mapping = Mapping::open(path, false)?;
+        let header = mapping.header();
+        let magic = unsafe { ptr::read_volatile(&header.magic) };
+        std::sync::atomic::fence(Ordering::Acquire);
+        if magic != MAGIC || header.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a frame channel or unsupported version",
+            ));
+        }
+        let api = Api::from_raw(header.api)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown graphics API"))?;
+        let name_len = header
+            .app_name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(NAME_LEN);
+        let info = PublisherInfo {
+            path: path.to_owned(),
+            pid: header.pid,
+            api,
+            app_name: String::from_utf8_lossy(&header.app_name[..name_len]).into_owned(),
+        };
+        let read_count = header.write_count.load(Ordering::Acquire);
+        Ok(Self {
+            mapping,
+            info,
+            read_count,
+        })
+    }
+
+    pub fn info(&self) -> &PublisherInfo {
+        &self.info
+    }
+
+    /// Most recently published timestamp, whether or not it has been read.
+    pub fn latest(&self) -> Option<u64> {
+        let header = self.mapping.header();
+        let write_count = header.write_count.load(Ordering::Acquire);
+        let newest = write_count.checked_sub(1)?;
+        Some(header.timestamps[newest as usize % CAPACITY].load(Ordering::Relaxed))
+    }
+
+    /// Appends timestamps published since the last call to `out`.
+    pub fn read_new(&mut self, out: &mut Vec<u64>) {
+        let header = self.mapping.header();
+        let write_count = header.write_count.load(Ordering::Acquire);
+        let start = self
+            .read_count
+            .max(write_count.saturating_sub(CAPACITY as u64));
+        for index in start..write_count {
+            out.push(header.timestamps[index as usize % CAPACITY].load(Ordering::Relaxed));
+        }
+        self.read_count = write_count;
+    }
+}
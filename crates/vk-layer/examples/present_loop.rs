@@ -0,0 +1,252 @@
+//! Minimal Vulkan application that presents to a headless surface, for
+//! exercising the layer without a display or GPU:
+//!
+//! ```sh
+//! cargo build -p rs_overlay_vk_layer
+//! VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json \
+//! VK_ADD_IMPLICIT_LAYER_PATH=crates/vk-layer \
+//! RS_OVERLAY_VK_LAYER=1 \
+//!     cargo run -p rs_overlay_vk_layer --example present_loop -- 600
+//! ```
+//!
+//! `VK_ADD_IMPLICIT_LAYER_PATH` must point at a directory containing
+//! `rs_overlay_vk_layer.json` whose `library_path` resolves to the built
+//! library, e.g. a copy with `"library_path": "../../target/debug/librs_overlay_vk_layer.so"`.
+
+use std::{
+    ffi::CStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ash::{ext, khr, vk};
+
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let frames: u64 = match std::env::args().nth(1) {
+        Some(count) => count.parse()?,
+        None => 600,
+    };
+
+    unsafe {
+        let entry = ash::Entry::load()?;
+        let app_name = c"rs_overlay present_loop";
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(app_name)
+            .api_version(vk::API_VERSION_1_1);
+        let extensions = [
+            khr::surface::NAME.as_ptr(),
+            ext::headless_surface::NAME.as_ptr(),
+        ];
+        let instance = entry.create_instance(
+            &vk::InstanceCreateInfo::default()
+                .application_info(&app_info)
+                .enabled_extension_names(&extensions),
+            None,
+        )?;
+
+        let headless = ext::headless_surface::Instance::new(&entry, &instance);
+        let surface_fn = khr::surface::Instance::new(&entry, &instance);
+        let surface =
+            headless.create_headless_surface(&vk::HeadlessSurfaceCreateInfoEXT::default(), None)?;
+
+        let (physical_device, queue_family) = instance
+            .enumerate_physical_devices()?
+            .into_iter()
+            .find_map(|device| {
+                instance
+                    .get_physical_device_queue_family_properties(device)
+                    .iter()
+                    .enumerate()
+                    .position(|(index, family)| {
+                        family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                            && surface_fn
+                                .get_physical_device_surface_support(device, index as u32, surface)
+                                .unwrap_or(false)
+                    })
+                    .map(|index| (device, index as u32))
+            })
+            .ok_or("no device can present to a headless surface")?;
+        let device_name = CStr::from_ptr(
+            instance
+                .get_physical_device_properties(physical_device)
+                .device_name
+                .as_ptr(),
+        );
+        println!(
+            "presenting {frames} frames on {}",
+            device_name.to_string_lossy()
+        );
+
+        let priorities = [1.0];
+        let queue_info = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family)
+            .queue_priorities(&priorities)];
+        let device_extensions = [khr::swapchain::NAME.as_ptr()];
+        let device = instance.create_device(
+            physical_device,
+            &vk::DeviceCreateInfo::default()
+                .queue_create_infos(&queue_info)
+                .enabled_extension_names(&device_extensions),
+            None,
+        )?;
+        let queue = device.get_device_queue(queue_family, 0);
+
+        let capabilities =
+            surface_fn.get_physical_device_surface_capabilities(physical_device, surface)?;
+        let format = surface_fn.get_physical_device_surface_formats(physical_device, surface)?[0];
+        let extent = match capabilities.current_extent.width {
+            u32::MAX => vk::Extent2D {
+                width: 640,
+                height: 480,
+            },
+            _ => capabilities.current_extent,
+        };
+        let swapchain_fn = khr::swapchain::Device::new(&instance, &device);
+        let swapchain = swapchain_fn.create_swapchain(
+            &vk::SwapchainCreateInfoKHR::default()
+                .surface(surface)
+                .min_image_count(capabilities.min_image_count.max(2))
+                .image_format(format.format)
+                .image_color_space(format.color_space)
+                .image_extent(extent)
+                .image_array_layers(1)
+                .image_usage(vk::ImageUsageFlags::TRANSFER_DST)
+                .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .pre_transform(capabilities.current_transform)
+                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .present_mode(vk::PresentModeKHR::FIFO)
+                .clipped(true),
+            None,
+        )?;
+        let images = swapchain_fn.get_swapchain_images(swapchain)?;
+
+        let pool = device.create_command_pool(
+            &vk::CommandPoolCreateInfo::default()
+                .queue_family_index(queue_family)
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+            None,
+        )?;
+        let command_buffer = device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .command_pool(pool)
+                .command_buffer_count(1),
+        )?[0];
+        let acquired = device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?;
+        let rendered = device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?;
+        let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+
+        for frame in 0..frames {
+            let started = Instant::now();
+            let (index, _) = swapchain_fn.acquire_next_image(
+                swapchain,
+                u64::MAX,
+                acquired,
+                vk::Fence::null(),
+            )?;
+            record_clear(&device, command_buffer, images[index as usize], frame)?;
+
+            let wait_stages = [vk::PipelineStageFlags::TRANSFER];
+            let command_buffers = [command_buffer];
+            let wait = [acquired];
+            let signal = [rendered];
+            device.queue_submit(
+                queue,
+                &[vk::SubmitInfo::default()
+                    .wait_semaphores(&wait)
+                    .wait_dst_stage_mask(&wait_stages)
+                    .command_buffers(&command_buffers)
+                    .signal_semaphores(&signal)],
+                fence,
+            )?;
+
+            let swapchains = [swapchain];
+            let indices = [index];
+            swapchain_fn.queue_present(
+                queue,
+                &vk::PresentInfoKHR::default()
+                    .wait_semaphores(&signal)
+                    .swapchains(&swapchains)
+                    .image_indices(&indices),
+            )?;
+            device.wait_for_fences(&[fence], true, u64::MAX)?;
+            device.reset_fences(&[fence])?;
+
+            if let Some(remaining) = FRAME_TIME.checked_sub(started.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+
+        device.device_wait_idle()?;
+        device.destroy_fence(fence, None);
+        device.destroy_semaphore(rendered, None);
+        device.destroy_semaphore(acquired, None);
+        device.destroy_command_pool(pool, None);
+        swapchain_fn.destroy_swapchain(swapchain, None);
+        device.destroy_device(None);
+        surface_fn.destroy_surface(surface, None);
+        instance.destroy_instance(None);
+    }
+    Ok(())
+}
+
+/// Clears `image` to a colour that cycles with `frame` and leaves it ready
+/// to present.
+unsafe fn record_clear(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    frame: u64,
+) -> ash::prelude::VkResult<()> {
+    let range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(1);
+    let shade = (frame % 120) as f32 / 120.0;
+    unsafe {
+        device.begin_command_buffer(
+            command_buffer,
+            &vk::CommandBufferBeginInfo::default()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+        )?;
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[vk::ImageMemoryBarrier::default()
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .image(image)
+                .subresource_range(range)],
+        );
+        device.cmd_clear_color_image(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &vk::ClearColorValue {
+                float32: [shade, 0.2, 1.0 - shade, 1.0],
+            },
+            &[range],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                .image(image)
+                .subresource_range(range)],
+        );
+        device.end_command_buffer(command_buffer)
+    }
+}
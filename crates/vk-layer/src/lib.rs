@@ -0,0 +1,397 @@
+//! Implicit Vulkan layer that timestamps every `vkQueuePresentKHR` and
+//! publishes the timestamps to rs_overlay through `rs_overlay_shm`.
+//!
+//! Install `librs_overlay_vk_layer.so` together with
+//! `rs_overlay_vk_layer.json` into a Vulkan implicit layer directory such as
+//! `~/.local/share/vulkan/implicit_layer.d/` and start the game with
+//! `RS_OVERLAY_VK_LAYER=1`. The manifest names the library relative to
+//! itself, so the two have to stay side by side.
+
+use std::{
+    collections::HashMap,
+    ffi::{CStr, c_char, c_void},
+    mem,
+    sync::{LazyLock, Mutex},
+};
+
+use ash::vk;
+use rs_overlay_shm::{Api, Publisher};
+
+/// `VK_LAYER_LINK_INFO` from `vk_layer.h`.
+const LAYER_LINK_INFO: u32 = 0;
+/// `LAYER_NEGOTIATE_INTERFACE_STRUCT` from `vk_layer.h`.
+const NEGOTIATE_INTERFACE_STRUCT: u32 = 1;
+const LAYER_INTERFACE_VERSION: u32 = 2;
+
+#[repr(C)]
+struct LayerInstanceLink {
+    next: *mut LayerInstanceLink,
+    next_get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    next_get_physical_device_proc_addr: *const c_void,
+}
+
+/// `VkLayerInstanceCreateInfo`; only the `pLayerInfo` member of its union
+/// is used.
+#[repr(C)]
+struct LayerInstanceCreateInfo {
+    s_type: vk::StructureType,
+    p_next: *const c_void,
+    function: u32,
+    layer_info: *mut LayerInstanceLink,
+}
+
+#[repr(C)]
+struct LayerDeviceLink {
+    next: *mut LayerDeviceLink,
+    next_get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    next_get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+}
+
+#[repr(C)]
+struct LayerDeviceCreateInfo {
+    s_type: vk::StructureType,
+    p_next: *const c_void,
+    function: u32,
+    layer_info: *mut LayerDeviceLink,
+}
+
+#[repr(C)]
+pub struct NegotiateLayerInterface {
+    s_type: u32,
+    p_next: *mut c_void,
+    loader_layer_interface_version: u32,
+    get_instance_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+    get_device_proc_addr: Option<vk::PFN_vkGetDeviceProcAddr>,
+    get_physical_device_proc_addr: *const c_void,
+}
+
+struct InstanceData {
+    get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    destroy_instance: vk::PFN_vkDestroyInstance,
+}
+
+struct DeviceData {
+    get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr,
+    destroy_device: vk::PFN_vkDestroyDevice,
+    queue_present: Option<vk::PFN_vkQueuePresentKHR>,
+}
+
+/// Keyed by the loader's dispatch table pointer, which is shared by an
+/// instance and its physical devices, and by a device and its queues.
+static INSTANCES: LazyLock<Mutex<HashMap<usize, InstanceData>>> = LazyLock::new(Default::default);
+static DEVICES: LazyLock<Mutex<HashMap<usize, DeviceData>>> = LazyLock::new(Default::default);
+static APP_NAME: Mutex<Option<String>> = Mutex::new(None);
+/// Created on the first present so processes that never present, such as
+/// `vulkaninfo`, leave nothing behind in `/dev/shm`.
+static PUBLISHER: Mutex<PublisherState> = Mutex::new(PublisherState::Unopened);
+
+enum PublisherState {
+    Unopened,
+    Open(Publisher),
+    /// Creating the channel failed; not retried on every present.
+    Failed,
+}
+
+unsafe fn dispatch_key(handle: impl vk::Handle) -> usize {
+    unsafe { *(handle.as_raw() as *const usize) }
+}
+
+/// Reinterprets the `PFN_vkVoidFunction` a `Get*ProcAddr` returned.
+unsafe fn cast_fn<F: Copy>(function: vk::PFN_vkVoidFunction) -> Option<F> {
+    debug_assert_eq!(
+        mem::size_of::<F>(),
+        mem::size_of::<unsafe extern "system" fn()>()
+    );
+    function.map(|function| unsafe { mem::transmute_copy(&function) })
+}
+
+unsafe fn as_void_fn<F: Copy>(function: F) -> vk::PFN_vkVoidFunction {
+    Some(unsafe { mem::transmute_copy::<F, unsafe extern "system" fn()>(&function) })
+}
+
+/// Entry point the Vulkan loader looks up in the layer manifest.
+///
+/// # Safety
+///
+/// `interface` must be null or point to a loader-owned
+/// `VkNegotiateLayerInterface`.
+#[unsafe(no_mangle)]
+pub unsafe extern "system" fn vkNegotiateLoaderLayerInterfaceVersion(
+    interface: *mut NegotiateLayerInterface,
+) -> vk::Result {
+    let Some(interface) = (unsafe { interface.as_mut() }) else {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    };
+    if interface.s_type != NEGOTIATE_INTERFACE_STRUCT
+        || interface.loader_layer_interface_version < LAYER_INTERFACE_VERSION
+    {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+    interface.loader_layer_interface_version = LAYER_INTERFACE_VERSION;
+    interface.get_instance_proc_addr = Some(get_instance_proc_addr);
+    interface.get_device_proc_addr = Some(get_device_proc_addr);
+    interface.get_physical_device_proc_addr = std::ptr::null();
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn get_instance_proc_addr(
+    instance: vk::Instance,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let name_str = unsafe { CStr::from_ptr(name) };
+    if let Some(function) = unsafe { intercepted(name_str) } {
+        return function;
+    }
+    if instance == vk::Instance::null() {
+        return None;
+    }
+    let next = INSTANCES
+        .lock()
+        .unwrap()
+        .get(&unsafe { dispatch_key(instance) })
+        .map(|data| data.get_instance_proc_addr)?;
+    unsafe { next(instance, name) }
+}
+
+unsafe extern "system" fn get_device_proc_addr(
+    device: vk::Device,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    let name_str = unsafe { CStr::from_ptr(name) };
+    let devices = DEVICES.lock().unwrap();
+    let data = devices.get(&unsafe { dispatch_key(device) })?;
+    match name_str.to_bytes() {
+        b"vkGetDeviceProcAddr" => unsafe {
+            as_void_fn::<vk::PFN_vkGetDeviceProcAddr>(get_device_proc_addr)
+        },
+        b"vkDestroyDevice" => unsafe { as_void_fn::<vk::PFN_vkDestroyDevice>(destroy_device) },
+        b"vkQueuePresentKHR" if data.queue_present.is_some() => unsafe {
+            as_void_fn::<vk::PFN_vkQueuePresentKHR>(queue_present)
+        },
+        _ => unsafe { (data.get_device_proc_addr)(device, name) },
+    }
+}
+
+/// Entry points this layer wraps, as returned from `vkGetInstanceProcAddr`.
+unsafe fn intercepted(name: &CStr) -> Option<vk::PFN_vkVoidFunction> {
+    let function = unsafe {
+        match name.to_bytes() {
+            b"vkGetInstanceProcAddr" => {
+                as_void_fn::<vk::PFN_vkGetInstanceProcAddr>(get_instance_proc_addr)
+            }
+            b"vkCreateInstance" => as_void_fn::<vk::PFN_vkCreateInstance>(create_instance),
+            b"vkDestroyInstance" => as_void_fn::<vk::PFN_vkDestroyInstance>(destroy_instance),
+            b"vkCreateDevice" => as_void_fn::<vk::PFN_vkCreateDevice>(create_device),
+            b"vkGetDeviceProcAddr" => {
+                as_void_fn::<vk::PFN_vkGetDeviceProcAddr>(get_device_proc_addr)
+            }
+            b"vkDestroyDevice" => as_void_fn::<vk::PFN_vkDestroyDevice>(destroy_device),
+            b"vkQueuePresentKHR" => as_void_fn::<vk::PFN_vkQueuePresentKHR>(queue_present),
+            _ => return None,
+        }
+    };
+    Some(function)
+}
+
+/// Finds the loader's link info in a create-info `pNext` chain.
+unsafe fn find_link_info<T>(mut next: *const c_void, s_type: vk::StructureType) -> *mut T {
+    while !next.is_null() {
+        let base = next.cast::<vk::BaseInStructure>();
+        let info = next.cast::<LayerInstanceCreateInfo>();
+        unsafe {
+            if (*base).s_type == s_type && (*info).function == LAYER_LINK_INFO {
+                return next.cast_mut().cast();
+            }
+            next = (*base).p_next.cast();
+        }
+    }
+    std::ptr::null_mut()
+}
+
+unsafe extern "system" fn create_instance(
+    create_info: *const vk::InstanceCreateInfo,
+    allocator: *const vk::AllocationCallbacks<'_>,
+    instance: *mut vk::Instance,
+) -> vk::Result {
+    unsafe {
+        let link_info: *mut LayerInstanceCreateInfo = find_link_info(
+            (*create_info).p_next,
+            vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+        );
+        if link_info.is_null() || (*link_info).layer_info.is_null() {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        }
+        let link = (*link_info).layer_info;
+        let next_get_instance_proc_addr = (*link).next_get_instance_proc_addr;
+        // Hand the rest of the chain to the next layer.
+        (*link_info).layer_info = (*link).next;
+
+        let Some(next_create): Option<vk::PFN_vkCreateInstance> = cast_fn(
+            next_get_instance_proc_addr(vk::Instance::null(), c"vkCreateInstance".as_ptr()),
+        ) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        let result = next_create(create_info, allocator, instance);
+        if result != vk::Result::SUCCESS {
+            return result;
+        }
+
+        let Some(destroy_instance): Option<vk::PFN_vkDestroyInstance> = cast_fn(
+            next_get_instance_proc_addr(*instance, c"vkDestroyInstance".as_ptr()),
+        ) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        INSTANCES.lock().unwrap().insert(
+            dispatch_key(*instance),
+            InstanceData {
+                get_instance_proc_addr: next_get_instance_proc_addr,
+                destroy_instance,
+            },
+        );
+        remember_app_name(create_info);
+        result
+    }
+}
+
+unsafe fn remember_app_name(create_info: *const vk::InstanceCreateInfo) {
+    let app_info = unsafe { (*create_info).p_application_info };
+    if app_info.is_null() {
+        return;
+    }
+    let name = unsafe { (*app_info).p_application_name };
+    if name.is_null() {
+        return;
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    if !name.is_empty() {
+        *APP_NAME.lock().unwrap() = Some(name.into_owned());
+    }
+}
+
+unsafe extern "system" fn destroy_instance(
+    instance: vk::Instance,
+    allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    if instance == vk::Instance::null() {
+        return;
+    }
+    let mut instances = INSTANCES.lock().unwrap();
+    let Some(data) = instances.remove(&unsafe { dispatch_key(instance) }) else {
+        return;
+    };
+    if instances.is_empty() {
+        *PUBLISHER.lock().unwrap() = PublisherState::Unopened;
+    }
+    drop(instances);
+    unsafe { (data.destroy_instance)(instance, allocator) };
+}
+
+unsafe extern "system" fn create_device(
+    physical_device: vk::PhysicalDevice,
+    create_info: *const vk::DeviceCreateInfo,
+    allocator: *const vk::AllocationCallbacks<'_>,
+    device: *mut vk::Device,
+) -> vk::Result {
+    unsafe {
+        let link_info: *mut LayerDeviceCreateInfo = find_link_info(
+            (*create_info).p_next,
+            vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+        );
+        if link_info.is_null() || (*link_info).layer_info.is_null() {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        }
+        let link = (*link_info).layer_info;
+        let next_get_instance_proc_addr = (*link).next_get_instance_proc_addr;
+        let next_get_device_proc_addr = (*link).next_get_device_proc_addr;
+        (*link_info).layer_info = (*link).next;
+
+        let Some(next_create): Option<vk::PFN_vkCreateDevice> = cast_fn(
+            next_get_instance_proc_addr(vk::Instance::null(), c"vkCreateDevice".as_ptr()),
+        ) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        let result = next_create(physical_device, create_info, allocator, device);
+        if result != vk::Result::SUCCESS {
+            return result;
+        }
+
+        let Some(destroy_device): Option<vk::PFN_vkDestroyDevice> = cast_fn(
+            next_get_device_proc_addr(*device, c"vkDestroyDevice".as_ptr()),
+        ) else {
+            return vk::Result::ERROR_INITIALIZATION_FAILED;
+        };
+        let queue_present = cast_fn(next_get_device_proc_addr(
+            *device,
+            c"vkQueuePresentKHR".as_ptr(),
+        ));
+        DEVICES.lock().unwrap().insert(
+            dispatch_key(*device),
+            DeviceData {
+                get_device_proc_addr: next_get_device_proc_addr,
+                destroy_device,
+                queue_present,
+            },
+        );
+        result
+    }
+}
+
+unsafe extern "system" fn destroy_device(
+    device: vk::Device,
+    allocator: *const vk::AllocationCallbacks<'_>,
+) {
+    if device == vk::Device::null() {
+        return;
+    }
+    let data = DEVICES
+        .lock()
+        .unwrap()
+        .remove(&unsafe { dispatch_key(device) });
+    if let Some(data) = data {
+        unsafe { (data.destroy_device)(device, allocator) };
+    }
+}
+
+unsafe extern "system" fn queue_present(
+    queue: vk::Queue,
+    present_info: *const vk::PresentInfoKHR<'_>,
+) -> vk::Result {
+    let next = DEVICES
+        .lock()
+        .unwrap()
+        .get(&unsafe { dispatch_key(queue) })
+        .and_then(|data| data.queue_present);
+    let Some(next) = next else {
+        return vk::Result::ERROR_DEVICE_LOST;
+    };
+    let result = unsafe { next(queue, present_info) };
+    // A failed present showed nothing, so it is no frame.
+    if matches!(result, vk::Result::SUCCESS | vk::Result::SUBOPTIMAL_KHR) {
+        publish_present();
+    }
+    result
+}
+
+fn publish_present() {
+    let mut state = PUBLISHER.lock().unwrap();
+    if let PublisherState::Unopened = *state {
+        *state = match Publisher::create(Api::Vulkan, &app_name()) {
+            Ok(publisher) => PublisherState::Open(publisher),
+            Err(_) => PublisherState::Failed,
+        };
+    }
+    if let PublisherState::Open(publisher) = &*state {
+        publisher.publish_now();
+    }
+}
+
+/// `VkApplicationInfo::pApplicationName`, or the process name for
+/// applications that do not set one.
+fn app_name() -> String {
+    if let Some(name) = APP_NAME.lock().unwrap().clone() {
+        return name;
+    }
+    std::fs::read_to_string("/proc/self/comm")
+        .map(|comm| comm.trim_end().to_owned())
+        .unwrap_or_default()
+}
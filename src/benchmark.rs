@@ -0,0 +1,63 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+
+use crate::{FpsTracker, fps::FrameStats};
+
+/// Frame times collected between starting and stopping a capture.
+pub(crate) struct Benchmark {
+    started: Instant,
+    /// End of the newest frame taken so far.
+    last: Instant,
+    times_ms: Vec<f32>,
+}
+
+impl Benchmark {
+    pub(crate) fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            times_ms: Vec::new(),
+        }
+    }
+
+    /// Takes the frames `fps` recorded since the last call. Called more often
+    /// than the tracker's history runs out, nothing is missed.
+    pub(crate) fn record(&mut self, fps: &FpsTracker) {
+        for (end, duration) in fps.frame_times() {
+            if end > self.last {
+                self.times_ms.push(duration.as_secs_f32() * 1000.0);
+                self.last = end;
+            }
+        }
+    }
+
+    /// Writes the capture to a new CSV file in `dir`, one frame time per
+    /// line, and returns its path and statistics.
+    pub(crate) fn save(self, dir: &Path) -> anyhow::Result<(PathBuf, FrameStats)> {
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let path = dir.join(format!("benchmark-{stamp}.csv"));
+
+        let mut csv = String::from("frame,frame_time_ms\n");
+        for (index, ms) in self.times_ms.iter().enumerate() {
+            let _ = writeln!(csv, "{index},{ms:.3}");
+        }
+        fs::write(&path, csv).with_context(|| format!("write {}", path.display()))?;
+
+        let mut times = self.times_ms;
+        Ok((path, FrameStats::from_frame_times(&mut times)))
+    }
+
+    pub(crate) fn elapsed(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+}
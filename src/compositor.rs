@@ -0,0 +1,40 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use winit::event_loop::{EventLoopProxy, EventLoopWindowTarget};
+
+use crate::event::OverlayEvent;
+
+/// Reports whether a compositing manager runs, which decides on X11 whether
+/// a transparent window is see-through at all. Dropping it stops the
+/// background thread.
+pub(crate) struct CompositorWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl CompositorWatcher {
+    /// `None` off X11, where the windowing system always composites.
+    pub(crate) fn start(
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if crate::x11::is_x11(target) {
+                let stop = Arc::new(AtomicBool::new(false));
+                crate::x11::spawn_compositor_watcher(proxy, stop.clone())?;
+                return Ok(Some(Self { stop }));
+            }
+        }
+        let _ = (target, proxy);
+        Ok(None)
+    }
+}
+
+impl Drop for CompositorWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,1122 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Deserializer, de};
+
+/// Contents of `config.toml`. Every section is optional; missing values fall
+/// back to the same defaults the overlay used before it was configurable.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub renderer: RendererConfig,
+    pub theme: ThemeConfig,
+    pub source: SourceConfig,
+    pub visibility: VisibilityConfig,
+    pub edit: EditConfig,
+    pub hotkeys: HotkeysConfig,
+    pub benchmark: BenchmarkConfig,
+    pub control: ControlConfig,
+    #[serde(rename = "widget")]
+    pub widgets: Vec<WidgetConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            renderer: RendererConfig::default(),
+            theme: ThemeConfig::default(),
+            source: SourceConfig::default(),
+            visibility: VisibilityConfig::default(),
+            edit: EditConfig::default(),
+            hotkeys: HotkeysConfig::default(),
+            benchmark: BenchmarkConfig::default(),
+            control: ControlConfig::default(),
+            widgets: vec![WidgetConfig::default()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    pub level: WindowLevel,
+    /// Monitors to cover, one overlay window each: a single selector or a
+    /// list of them.
+    #[serde(deserialize_with = "one_or_many")]
+    pub monitor: Vec<MonitorSelector>,
+    /// Attaches a single overlay window to this X11 window instead of
+    /// covering monitors. It follows the window's position, size and
+    /// mapping, and stacks just above it.
+    pub follow: Option<WindowSelector>,
+    /// Opens X11 windows that the window manager neither decorates, places
+    /// nor stacks, so it cannot put a fullscreen window on top of them.
+    /// Reopens the windows when changed.
+    pub override_redirect: bool,
+}
+
+impl WindowConfig {
+    /// `level`, except that an overlay following a window stacks normally
+    /// so whatever covers the window covers the overlay too.
+    pub fn effective_level(&self) -> WindowLevel {
+        match self.follow {
+            Some(_) => WindowLevel::Normal,
+            None => self.level,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "rs_overlay".to_owned(),
+            level: WindowLevel::AlwaysOnTop,
+            monitor: vec![MonitorSelector::Primary],
+            follow: None,
+            override_redirect: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowLevel {
+    AlwaysOnTop,
+    Normal,
+    AlwaysOnBottom,
+}
+
+impl From<WindowLevel> for winit::window::WindowLevel {
+    fn from(level: WindowLevel) -> Self {
+        match level {
+            WindowLevel::AlwaysOnTop => Self::AlwaysOnTop,
+            WindowLevel::Normal => Self::Normal,
+            WindowLevel::AlwaysOnBottom => Self::AlwaysOnBottom,
+        }
+    }
+}
+
+/// One or more monitors: `"primary"`, `"all"`, a zero-based index, or a
+/// monitor name as reported by the windowing system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorSelector {
+    Primary,
+    All,
+    Index(usize),
+    Name(String),
+}
+
+impl<'de> Deserialize<'de> for MonitorSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Index(usize),
+            Name(String),
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Index(index) => Self::Index(index),
+            Raw::Name(name) if name == "primary" => Self::Primary,
+            Raw::Name(name) if name == "all" => Self::All,
+            Raw::Name(name) => Self::Name(name),
+        })
+    }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<MonitorSelector>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        One(MonitorSelector),
+        Many(Vec<MonitorSelector>),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::One(selector) => Ok(vec![selector]),
+        Raw::Many(selectors) if selectors.is_empty() => {
+            Err(de::Error::custom("expected at least one monitor"))
+        }
+        Raw::Many(selectors) => Ok(selectors),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    pub backend: RendererBackend,
+    pub present_mode: PresentMode,
+    /// Upper bound on overlay redraws per second, on top of the widgets'
+    /// own refresh intervals.
+    pub max_fps: Option<f32>,
+}
+
+impl RendererConfig {
+    /// Shortest time between two redraws, if `max_fps` limits it.
+    pub fn min_frame_interval(&self) -> Option<Duration> {
+        self.max_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f32(1.0 / fps))
+    }
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            backend: RendererBackend::Auto,
+            present_mode: PresentMode::Fifo,
+            max_fps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RendererBackend {
+    /// The GPU through wgpu, or the software renderer when no adapter works.
+    #[default]
+    Auto,
+    Gpu,
+    /// Rasterizes on the CPU and copies the result into the window. Needs
+    /// X11.
+    Software,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+    AutoVsync,
+    AutoNoVsync,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::Mailbox => Self::Mailbox,
+            PresentMode::Immediate => Self::Immediate,
+            PresentMode::AutoVsync => Self::AutoVsync,
+            PresentMode::AutoNoVsync => Self::AutoNoVsync,
+        }
+    }
+}
+
+/// Where frame timings come from.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    /// Nothing to count, so the frame widgets show no values. The overlay's
+    /// own redraws follow its schedule rather than any game's frames.
+    #[default]
+    None,
+    /// Presents of another X11 window, counted with the Present or Damage
+    /// extension.
+    X11(X11SourceOptions),
+    /// Timestamps published by the rs_overlay Vulkan layer or GL preload hook
+    /// running inside another process.
+    Hook(HookSourceOptions),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct X11SourceOptions {
+    #[serde(flatten)]
+    pub window: WindowSelector,
+    #[serde(default)]
+    pub method: CaptureMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMethod {
+    /// Present `CompleteNotify` events, one per presented frame. Falls back
+    /// to `damage` when the server lacks the Present extension.
+    #[default]
+    Present,
+    /// Damage notifications, which also fire for partial redraws.
+    Damage,
+}
+
+/// When the widgets are shown. Every rule that is set has to hold; with none
+/// set they always are.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisibilityConfig {
+    /// The focused window (`_NET_ACTIVE_WINDOW`) has to match.
+    pub focused: Option<WindowSelector>,
+    /// The focused window has to be fullscreen.
+    pub fullscreen: bool,
+    /// Regular expression a running process name has to match.
+    pub process: Option<Pattern>,
+    /// How long widgets take to fade in or out, in milliseconds.
+    pub fade_ms: u64,
+}
+
+impl VisibilityConfig {
+    pub fn is_empty(&self) -> bool {
+        self.focused.is_none() && !self.fullscreen && self.process.is_none()
+    }
+
+    /// Whether the rules look at the focused window, which needs X11.
+    pub fn needs_focus(&self) -> bool {
+        self.focused.is_some() || self.fullscreen
+    }
+
+    pub fn fade(&self) -> Duration {
+        Duration::from_millis(self.fade_ms)
+    }
+}
+
+impl Default for VisibilityConfig {
+    fn default() -> Self {
+        Self {
+            focused: None,
+            fullscreen: false,
+            process: None,
+            fade_ms: 200,
+        }
+    }
+}
+
+/// Edit mode, toggled by sending the overlay `SIGUSR1`, lets the widgets be
+/// dragged and resized with the mouse and saves where they end up.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditConfig {
+    /// Spacing, in points, that dragged offsets snap to; 0 turns snapping
+    /// off.
+    pub grid: f32,
+}
+
+impl Default for EditConfig {
+    fn default() -> Self {
+        Self { grid: 8.0 }
+    }
+}
+
+/// Global key bindings, grabbed on X11 so they work while a game has the
+/// focus.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeysConfig {
+    /// Hides the widgets, or shows them again where `[visibility]` allows.
+    pub toggle_visibility: Option<KeyBinding>,
+    pub toggle_edit_mode: Option<KeyBinding>,
+    /// Switches to the next layout the widgets name.
+    pub next_layout: Option<KeyBinding>,
+    /// Starts capturing frame times, or stops and saves the capture.
+    pub toggle_benchmark: Option<KeyBinding>,
+}
+
+impl HotkeysConfig {
+    pub fn bindings(&self) -> Vec<(KeyBinding, HotkeyAction)> {
+        [
+            (&self.toggle_visibility, HotkeyAction::ToggleVisibility),
+            (&self.toggle_edit_mode, HotkeyAction::ToggleEditMode),
+            (&self.next_layout, HotkeyAction::NextLayout),
+            (&self.toggle_benchmark, HotkeyAction::ToggleBenchmark),
+        ]
+        .into_iter()
+        .filter_map(|(binding, action)| Some((binding.clone()?, action)))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    ToggleVisibility,
+    ToggleEditMode,
+    NextLayout,
+    ToggleBenchmark,
+}
+
+/// A key and the modifiers held with it, such as `"Ctrl+Shift+F12"`.
+/// Modifiers are `Shift`, `Ctrl`, `Alt`, `Super` and `AltGr`; the key is a
+/// single character or a keysym name such as `F12`, `space` or `Page_Up`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub modifiers: Vec<KeyModifier>,
+    /// The key's X keysym.
+    pub keysym: u32,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyModifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Super,
+    AltGr,
+}
+
+impl KeyBinding {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key = parts.pop()?;
+        let modifiers = parts
+            .into_iter()
+            .map(|name| match name.to_ascii_lowercase().as_str() {
+                "shift" => Some(KeyModifier::Shift),
+                "ctrl" | "control" => Some(KeyModifier::Ctrl),
+                "alt" => Some(KeyModifier::Alt),
+                "super" | "win" | "mod4" => Some(KeyModifier::Super),
+                "altgr" => Some(KeyModifier::AltGr),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            modifiers,
+            keysym: keysym(key)?,
+            text: text.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).ok_or_else(|| {
+            de::Error::custom(format!(
+                "invalid key binding `{text}`, expected modifiers and a key like `Ctrl+Shift+F12`"
+            ))
+        })
+    }
+}
+
+/// Keysym of a printable ASCII character, which matches its code, or of a
+/// named key.
+fn keysym(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(char), None) = (chars.next(), chars.next()) {
+        return char
+            .is_ascii_graphic()
+            .then(|| u32::from(char.to_ascii_lowercase()));
+    }
+    if let Some(number) = name
+        .strip_prefix(['F', 'f'])
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| (1..=35).contains(number))
+    {
+        return Some(0xffbe + number - 1);
+    }
+    const NAMED: &[(&str, u32)] = &[
+        ("space", 0x0020),
+        ("Escape", 0xff1b),
+        ("Tab", 0xff09),
+        ("Return", 0xff0d),
+        ("BackSpace", 0xff08),
+        ("Delete", 0xffff),
+        ("Insert", 0xff63),
+        ("Home", 0xff50),
+        ("End", 0xff57),
+        ("Page_Up", 0xff55),
+        ("Page_Down", 0xff56),
+        ("Left", 0xff51),
+        ("Up", 0xff52),
+        ("Right", 0xff53),
+        ("Down", 0xff54),
+        ("Print", 0xff61),
+        ("Pause", 0xff13),
+        ("Scroll_Lock", 0xff14),
+    ];
+    NAMED
+        .iter()
+        .find(|(named, _)| named.eq_ignore_ascii_case(name))
+        .map(|&(_, keysym)| keysym)
+}
+
+/// Where benchmark captures go.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BenchmarkConfig {
+    /// Defaults to `$XDG_DATA_HOME/rs_overlay/benchmarks`, falling back to
+    /// `~/.local/share/rs_overlay/benchmarks`.
+    pub output_dir: Option<PathBuf>,
+}
+
+impl BenchmarkConfig {
+    pub fn output_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.output_dir {
+            return Some(dir.clone());
+        }
+        let base = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share"))
+            })?;
+        Some(base.join("rs_overlay").join("benchmarks"))
+    }
+}
+
+/// The JSON-RPC socket scripts drive a running overlay through.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    pub enabled: bool,
+    /// Defaults to `$XDG_RUNTIME_DIR/rs_overlay.sock`.
+    pub socket: Option<PathBuf>,
+}
+
+impl ControlConfig {
+    pub fn socket_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.socket {
+            return Some(path.clone());
+        }
+        let dir = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+        Some(Path::new(&dir).join("rs_overlay.sock"))
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            socket: None,
+        }
+    }
+}
+
+/// Picks one of the running hooked processes. Every key that is set has to
+/// match; among several matches the one that presented most recently wins.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSourceOptions {
+    pub pid: Option<u32>,
+    /// Regular expression searched for in the application name.
+    pub app: Option<Pattern>,
+    pub api: Option<HookApi>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookApi {
+    Vulkan,
+    Gl,
+}
+
+/// Picks a top-level X11 window. Every key that is set has to match.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSelector {
+    /// `_NET_WM_PID` of the window.
+    pub pid: Option<u32>,
+    /// Either half of `WM_CLASS`, compared exactly.
+    pub class: Option<String>,
+    /// Regular expression searched for in the window title.
+    pub title: Option<Pattern>,
+}
+
+impl WindowSelector {
+    pub fn is_empty(&self) -> bool {
+        self.pid.is_none() && self.class.is_none() && self.title.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern(pub regex::Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        regex::Regex::new(&text)
+            .map(Self)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Defaults shared by all widgets unless a widget overrides them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub font: FontFamily,
+    pub font_size: f32,
+    pub color: Color,
+    /// Styles of the classes command widgets report, by class name, as in
+    /// `[theme.classes.warning]`. Setting any replaces the built-in
+    /// `warning` and `critical` ones.
+    pub classes: HashMap<String, ClassStyle>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            font: FontFamily::Proportional,
+            font_size: 18.0,
+            color: Color(egui::Color32::from_gray(140)),
+            classes: HashMap::from([
+                (
+                    "warning".to_owned(),
+                    ClassStyle {
+                        color: Some(Color(egui::Color32::from_rgb(224, 160, 48))),
+                        background: None,
+                    },
+                ),
+                (
+                    "critical".to_owned(),
+                    ClassStyle {
+                        color: Some(Color(egui::Color32::from_rgb(224, 64, 64))),
+                        background: None,
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+/// Overrides for text a command widget marks with a class.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassStyle {
+    pub color: Option<Color>,
+    pub background: Option<Color>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontFamily {
+    Proportional,
+    Monospace,
+}
+
+impl From<FontFamily> for egui::FontFamily {
+    fn from(family: FontFamily) -> Self {
+        match family {
+            FontFamily::Proportional => Self::Proportional,
+            FontFamily::Monospace => Self::Monospace,
+        }
+    }
+}
+
+/// One `[[widget]]` table. The `type` key selects the widget and its
+/// type-specific keys sit alongside the shared ones below. Whatever the
+/// shared keys leave over goes to the widget's options, which reject keys
+/// they do not know.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WidgetConfig {
+    #[serde(flatten)]
+    pub kind: WidgetKind,
+    /// Name the control socket can address the widget by, besides its
+    /// position in the file.
+    pub id: Option<String>,
+    #[serde(default)]
+    pub anchor: Anchor,
+    #[serde(default = "default_offset")]
+    pub offset: [f32; 2],
+    /// Size relative to how the widget lays itself out. Written `zoom`, as
+    /// graphs already take `scale` for their y axis.
+    #[serde(rename = "zoom", default = "default_scale")]
+    pub scale: f32,
+    pub font: Option<FontFamily>,
+    pub font_size: Option<f32>,
+    pub color: Option<Color>,
+    /// Minimum time between widget updates, in milliseconds.
+    pub refresh_ms: Option<u64>,
+    /// Overlay windows that show the widget. Unset, or matching none of the
+    /// open windows, puts it on the first one.
+    pub monitor: Option<MonitorSelector>,
+    /// Layouts that show the widget; unset shows it in all of them.
+    #[serde(default)]
+    pub layouts: Vec<String>,
+}
+
+impl WidgetConfig {
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.refresh_ms.map(Duration::from_millis)
+    }
+}
+
+impl Default for WidgetConfig {
+    fn default() -> Self {
+        Self {
+            kind: WidgetKind::Fps(FpsOptions::default()),
+            id: None,
+            anchor: Anchor::default(),
+            offset: default_offset(),
+            scale: default_scale(),
+            font: None,
+            font_size: None,
+            color: None,
+            refresh_ms: None,
+            monitor: None,
+            layouts: Vec::new(),
+        }
+    }
+}
+
+fn default_offset() -> [f32; 2] {
+    [12.0, 12.0]
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WidgetKind {
+    Fps(FpsOptions),
+    Graph(GraphOptions),
+    Text(TextOptions),
+    I3bar(I3barOptions),
+    Command(CommandOptions),
+}
+
+impl WidgetKind {
+    /// The `type` key that selects the widget.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fps(_) => "fps",
+            Self::Graph(_) => "graph",
+            Self::Text(_) => "text",
+            Self::I3bar(_) => "i3bar",
+            Self::Command(_) => "command",
+        }
+    }
+
+    /// How far back the widget reads frame times, for widgets that do.
+    pub fn frame_history(&self) -> Option<Duration> {
+        match self {
+            Self::Fps(options) => Some(options.window()),
+            Self::Graph(options) if options.metric.is_none() => Some(options.span()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FpsOptions {
+    /// Values to show, one per line.
+    pub metrics: Vec<FpsMetric>,
+    /// Span of recent frames the statistics are computed over.
+    pub window_ms: u64,
+}
+
+impl FpsOptions {
+    pub fn window(&self) -> Duration {
+        Duration::from_millis(self.window_ms)
+    }
+}
+
+impl Default for FpsOptions {
+    fn default() -> Self {
+        Self {
+            metrics: vec![FpsMetric::Fps],
+            window_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FpsMetric {
+    /// Frames per second over the last one-second window.
+    Fps,
+    /// Frame times in milliseconds.
+    Min,
+    Max,
+    Mean,
+    StdDev,
+    P50,
+    P95,
+    P99,
+    /// Frame rate of the slowest 1% and 0.1% of frames.
+    #[serde(rename = "low_1")]
+    Low1,
+    #[serde(rename = "low_0_1")]
+    Low01,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphOptions {
+    /// How much history the graph shows, in milliseconds.
+    pub span_ms: u64,
+    pub width: f32,
+    pub height: f32,
+    pub scale: GraphScale,
+    /// Plots the samples pushed to this metric over the control socket
+    /// instead of frame times. `scale`, `reference_ms` and `spike_ms` are
+    /// then in the metric's unit.
+    pub metric: Option<String>,
+    /// Frame times, in milliseconds, drawn as horizontal reference lines.
+    pub reference_ms: Vec<f32>,
+    /// Frames slower than this are highlighted with `spike_color`.
+    pub spike_ms: Option<f32>,
+    pub spike_color: Color,
+    pub background: Option<Color>,
+}
+
+impl GraphOptions {
+    pub fn span(&self) -> Duration {
+        Duration::from_millis(self.span_ms)
+    }
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        Self {
+            span_ms: 5000,
+            width: 240.0,
+            height: 80.0,
+            scale: GraphScale::Auto,
+            metric: None,
+            reference_ms: vec![16.6, 33.3],
+            spike_ms: Some(33.3),
+            spike_color: Color(egui::Color32::from_rgba_unmultiplied(220, 60, 60, 90)),
+            background: Some(Color(egui::Color32::from_black_alpha(96))),
+        }
+    }
+}
+
+/// Upper bound of the graph's y axis: `"auto"` or a fixed number of
+/// milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphScale {
+    Auto,
+    Fixed(f32),
+}
+
+impl<'de> Deserialize<'de> for GraphScale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Fixed(f32),
+            Name(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Fixed(ms) if ms > 0.0 => Ok(Self::Fixed(ms)),
+            Raw::Name(name) if name == "auto" => Ok(Self::Auto),
+            _ => Err(de::Error::custom(
+                "expected `\"auto\"` or a positive number of milliseconds",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextOptions {
+    /// Shown until the control socket sets something else.
+    pub text: String,
+}
+
+/// Status line of an i3bar protocol producer such as i3status, i3blocks or
+/// i3status-rust.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct I3barOptions {
+    /// Shell command that writes the protocol to its standard output.
+    pub command: String,
+    #[serde(default = "default_separator_color")]
+    pub separator_color: Color,
+    /// Colours of blocks marked `urgent`.
+    #[serde(default = "default_urgent_background")]
+    pub urgent_background: Color,
+    #[serde(default = "default_urgent_color")]
+    pub urgent_color: Color,
+}
+
+fn default_separator_color() -> Color {
+    Color(egui::Color32::from_gray(102))
+}
+
+fn default_urgent_background() -> Color {
+    Color(egui::Color32::from_rgb(144, 0, 0))
+}
+
+fn default_urgent_color() -> Color {
+    Color(egui::Color32::WHITE)
+}
+
+/// Output of a script written for Waybar's custom module: per line, a JSON
+/// object with `text`, `tooltip`, `class` and `percentage`, or plain text.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandOptions {
+    /// Shell command that prints the output.
+    pub command: String,
+    /// Runs the command again this long after the last run started, in
+    /// milliseconds. Unset runs it once and shows each line as it comes.
+    #[serde(default, deserialize_with = "positive_interval")]
+    pub interval_ms: Option<u64>,
+    /// Draws `percentage` as a bar under the text.
+    #[serde(default)]
+    pub bar: bool,
+    #[serde(default = "default_bar_size")]
+    pub bar_size: [f32; 2],
+    #[serde(default = "default_bar_background")]
+    pub bar_background: Color,
+}
+
+impl CommandOptions {
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval_ms.map(Duration::from_millis)
+    }
+}
+
+/// A zero interval would start the command again on every frame.
+fn positive_interval<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("expected an interval above 0")),
+        interval => Ok(Some(interval)),
+    }
+}
+
+fn default_bar_size() -> [f32; 2] {
+    [120.0, 6.0]
+}
+
+fn default_bar_background() -> Color {
+    Color(egui::Color32::from_black_alpha(96))
+}
+
+/// Corner or edge of the monitor a widget's offset is measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// egui alignment for the anchor plus `offset` pointing away from the edge.
+    pub fn to_egui(self, offset: [f32; 2]) -> (egui::Align2, egui::Vec2) {
+        let align = match self {
+            Self::TopLeft => egui::Align2::LEFT_TOP,
+            Self::Top => egui::Align2::CENTER_TOP,
+            Self::TopRight => egui::Align2::RIGHT_TOP,
+            Self::Left => egui::Align2::LEFT_CENTER,
+            Self::Center => egui::Align2::CENTER_CENTER,
+            Self::Right => egui::Align2::RIGHT_CENTER,
+            Self::BottomLeft => egui::Align2::LEFT_BOTTOM,
+            Self::Bottom => egui::Align2::CENTER_BOTTOM,
+            Self::BottomRight => egui::Align2::RIGHT_BOTTOM,
+        };
+        (align, egui::Vec2::from(offset) * self.direction())
+    }
+
+    /// Per axis, `1.0` where a growing offset moves right or down and `-1.0`
+    /// where it moves left or up, away from a right or bottom edge.
+    pub fn direction(self) -> egui::Vec2 {
+        match self {
+            Self::TopLeft | Self::Top | Self::Left | Self::Center => egui::vec2(1.0, 1.0),
+            Self::TopRight | Self::Right => egui::vec2(-1.0, 1.0),
+            Self::BottomLeft | Self::Bottom => egui::vec2(1.0, -1.0),
+            Self::BottomRight => egui::vec2(-1.0, -1.0),
+        }
+    }
+}
+
+/// `#rrggbb` or `#rrggbbaa` colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub egui::Color32);
+
+impl Color {
+    pub fn parse(text: &str) -> Option<Self> {
+        let hex = text.strip_prefix('#')?;
+        if !hex.is_ascii() || !matches!(hex.len(), 6 | 8) {
+            return None;
+        }
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+        let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+        Some(Self(egui::Color32::from_rgba_unmultiplied(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            alpha,
+        )))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).ok_or_else(|| {
+            de::Error::custom(format!(
+                "invalid colour `{text}`, expected `#rrggbb` or `#rrggbbaa`"
+            ))
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, .. } => write!(f, "failed to read {}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/rs_overlay/config.toml`, falling back to
+    /// `~/.config/rs_overlay/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("rs_overlay").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::parse(path, &text)
+    }
+
+    pub fn parse(path: &Path, text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|err| {
+            let offset = err.span().map_or(0, |span| span.start);
+            let (line, column) = line_column(text, offset);
+            ConfigError::Parse {
+                path: path.to_owned(),
+                line,
+                column,
+                message: err.message().to_owned(),
+            }
+        })
+    }
+}
+
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, ConfigError> {
+        Config::parse(Path::new("config.toml"), text)
+    }
+
+    #[test]
+    fn widgets_reject_unknown_keys() {
+        let err = parse("[[widget]]\ntype = \"fps\"\nofset = [1, 2]\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `ofset`"), "{err}");
+        assert!(parse("[[widget]]\ntype = \"text\"\nwindow_ms = 500\n").is_err());
+    }
+
+    #[test]
+    fn window_selectors_reject_unknown_keys() {
+        let err = parse("[source]\ntype = \"x11\"\nclas = \"steam_app_1\"\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `clas`"), "{err}");
+        assert!(parse("[window.follow]\nclas = \"steam_app_1\"\n").is_err());
+        let config =
+            parse("[source]\ntype = \"x11\"\nclass = \"steam_app_1\"\nmethod = \"damage\"\n")
+                .unwrap();
+        let SourceConfig::X11(options) = &config.source else {
+            panic!("not an x11 source");
+        };
+        assert_eq!(options.window.class.as_deref(), Some("steam_app_1"));
+        assert_eq!(options.method, CaptureMethod::Damage);
+    }
+
+    #[test]
+    fn graph_scale_is_not_the_widget_zoom() {
+        let config = parse("[[widget]]\ntype = \"graph\"\nscale = 50\nzoom = 1.5\n").unwrap();
+        let widget = &config.widgets[0];
+        assert_eq!(widget.scale, 1.5);
+        let WidgetKind::Graph(options) = &widget.kind else {
+            panic!("not a graph");
+        };
+        assert_eq!(options.scale, GraphScale::Fixed(50.0));
+    }
+
+    #[test]
+    fn command_interval_is_positive() {
+        let command = "[[widget]]\ntype = \"command\"\ncommand = \"date\"\n";
+        assert!(parse(&format!("{command}interval_ms = 0\n")).is_err());
+        let config = parse(&format!("{command}interval_ms = 500\n")).unwrap();
+        let WidgetKind::Command(options) = &config.widgets[0].kind else {
+            panic!("not a command");
+        };
+        assert_eq!(options.interval(), Some(Duration::from_millis(500)));
+        let config = parse(command).unwrap();
+        let WidgetKind::Command(options) = &config.widgets[0].kind else {
+            panic!("not a command");
+        };
+        assert_eq!(options.interval(), None);
+    }
+
+    #[test]
+    fn widgets_take_shared_and_own_keys() {
+        let config =
+            parse("[[widget]]\ntype = \"fps\"\noffset = [1, 2]\nid = \"fps\"\nwindow_ms = 30000\n")
+                .unwrap();
+        let widget = &config.widgets[0];
+        assert_eq!(widget.offset, [1.0, 2.0]);
+        assert_eq!(widget.kind.frame_history(), Some(Duration::from_secs(30)));
+    }
+}
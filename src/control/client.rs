@@ -0,0 +1,69 @@
+use std::{
+    io::{BufRead as _, BufReader, Write as _},
+    os::unix::net::UnixStream,
+    path::Path,
+};
+
+use anyhow::{Context as _, bail};
+use serde_json::Value;
+
+use super::{ControlRequest, RpcError};
+use crate::config::ControlConfig;
+
+/// A connection to a running overlay's control socket.
+pub struct ControlClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl ControlClient {
+    /// Connects to the socket `config` names.
+    pub fn connect(config: &ControlConfig) -> anyhow::Result<Self> {
+        let path = config
+            .socket_path()
+            .context("XDG_RUNTIME_DIR is not set; set [control] socket")?;
+        Self::connect_path(&path)
+    }
+
+    /// Connects to the socket at `path`.
+    pub fn connect_path(path: &Path) -> anyhow::Result<Self> {
+        let writer = UnixStream::connect(path).with_context(|| {
+            format!(
+                "connect to {}; is the overlay running with [control] enabled?",
+                path.display()
+            )
+        })?;
+        let reader = BufReader::new(writer.try_clone().context("clone control socket")?);
+        Ok(Self {
+            reader,
+            writer,
+            next_id: 0,
+        })
+    }
+
+    /// Sends `request` and waits for its result.
+    pub fn call(&mut self, request: &ControlRequest) -> anyhow::Result<Value> {
+        self.next_id += 1;
+        let mut message = serde_json::to_value(request).context("encode request")?;
+        message["jsonrpc"] = "2.0".into();
+        message["id"] = self.next_id.into();
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .context("send request")?;
+
+        line.clear();
+        if self.reader.read_line(&mut line).context("read response")? == 0 {
+            bail!("the overlay closed the connection");
+        }
+        let mut response: Value = serde_json::from_str(&line).context("decode response")?;
+        if let Some(error) = response.get_mut("error") {
+            let error: RpcError =
+                serde_json::from_value(error.take()).context("decode error response")?;
+            bail!("{error}");
+        }
+        Ok(response["result"].take())
+    }
+}
@@ -0,0 +1,265 @@
+#[cfg(unix)]
+mod client;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod server;
+
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use winit::event_loop::EventLoopProxy;
+
+#[cfg(unix)]
+pub use client::ControlClient;
+
+use crate::{config::ControlConfig, event::OverlayEvent};
+
+/// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// The `method` of each `ControlRequest` variant.
+const METHODS: &[&str] = &[
+    "list_widgets",
+    "set_text",
+    "push",
+    "show",
+    "hide",
+    "layout",
+    "toast",
+    "stats",
+    "load_config",
+];
+
+/// A widget by its position among the overlay's widgets or by its `id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WidgetRef {
+    Index(usize),
+    Id(String),
+}
+
+impl fmt::Display for WidgetRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Id(id) => write!(f, "`{id}`"),
+        }
+    }
+}
+
+/// What a client can ask a running overlay, as a JSON-RPC method and its
+/// params.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Every widget with its index, id, type, layouts and whether it shows.
+    ListWidgets {},
+    /// Replaces what a `text` widget shows.
+    SetText {
+        widget: WidgetRef,
+        text: String,
+    },
+    /// Appends samples to a metric, which `graph` widgets can plot.
+    Push {
+        metric: String,
+        values: Vec<f32>,
+    },
+    /// Shows a widget hidden by `hide`, or the whole overlay without
+    /// `widget`.
+    Show {
+        widget: Option<WidgetRef>,
+    },
+    Hide {
+        widget: Option<WidgetRef>,
+    },
+    /// Switches to layout `name` when given. Answers with the current layout
+    /// and all of them.
+    Layout {
+        name: Option<String>,
+    },
+    Toast {
+        message: String,
+        #[serde(default)]
+        error: bool,
+        duration_ms: Option<u64>,
+    },
+    /// Frame statistics over the last `window_ms`, one second by default.
+    Stats {
+        window_ms: Option<u64>,
+    },
+    /// Applies the config file at the absolute `path` and watches it from
+    /// then on.
+    LoadConfig {
+        path: PathBuf,
+    },
+}
+
+impl ControlRequest {
+    fn parse(mut request: Map<String, Value>) -> Result<Self, RpcError> {
+        let method = match request.remove("method") {
+            Some(Value::String(method)) => method,
+            _ => return Err(RpcError::new(INVALID_REQUEST, "the request has no method")),
+        };
+        if !METHODS.contains(&method.as_str()) {
+            return Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("no method `{method}`"),
+            ));
+        }
+        let params = match request.remove("params") {
+            None | Some(Value::Null) => Value::Object(Map::new()),
+            Some(params) => params,
+        };
+        serde_json::from_value(json!({ "method": method, "params": params }))
+            .map_err(|err| RpcError::invalid_params(err.to_string()))
+    }
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// A request on its way to the event loop, and where its answer goes.
+#[derive(Debug)]
+pub(crate) struct ControlCall {
+    pub(crate) request: ControlRequest,
+    pub(crate) reply: mpsc::Sender<Result<Value, RpcError>>,
+}
+
+/// Answers JSON-RPC 2.0 requests on a Unix socket only the user running the
+/// overlay can connect to, one request or batch per line. Dropping it
+/// removes the socket.
+pub(crate) struct ControlServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// `None` when `[control]` is disabled.
+    pub(crate) fn start(
+        config: &ControlConfig,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            use anyhow::Context as _;
+
+            let path = config
+                .socket_path()
+                .context("XDG_RUNTIME_DIR is not set; set [control] socket")?;
+            let stop = Arc::new(AtomicBool::new(false));
+            let thread = server::spawn(path, proxy, stop.clone())?;
+            Ok(Some(Self {
+                stop,
+                thread: Some(thread),
+            }))
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            let _ = proxy;
+            anyhow::bail!("the control socket needs a Unix system")
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answers one line from a client: a request, or a batch of them. `None`
+/// when there is nothing to send back, as for notifications.
+fn handle_message(line: &[u8], proxy: &EventLoopProxy<OverlayEvent>) -> Option<Value> {
+    let message = match serde_json::from_slice(line) {
+        Ok(message) => message,
+        Err(err) => {
+            return Some(response(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, err.to_string())),
+            ));
+        }
+    };
+    match message {
+        Value::Array(batch) if !batch.is_empty() => {
+            let responses: Vec<Value> = batch
+                .into_iter()
+                .filter_map(|message| handle_request(message, proxy))
+                .collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        message => handle_request(message, proxy),
+    }
+}
+
+/// Requests without an `id` are notifications and get no response.
+fn handle_request(message: Value, proxy: &EventLoopProxy<OverlayEvent>) -> Option<Value> {
+    let Value::Object(mut request) = message else {
+        return Some(response(
+            Value::Null,
+            Err(RpcError::new(INVALID_REQUEST, "expected a request object")),
+        ));
+    };
+    let id = request.remove("id");
+    let result = ControlRequest::parse(request).and_then(|request| call(request, proxy));
+    id.map(|id| response(id, result))
+}
+
+/// Hands `request` to the event loop and waits for its answer.
+fn call(request: ControlRequest, proxy: &EventLoopProxy<OverlayEvent>) -> Result<Value, RpcError> {
+    let (reply, answer) = mpsc::channel();
+    proxy
+        .send_event(OverlayEvent::Control(ControlCall { request, reply }))
+        .map_err(|_| RpcError::new(INTERNAL_ERROR, "the overlay is exiting"))?;
+    answer
+        .recv()
+        .unwrap_or_else(|_| Err(RpcError::new(INTERNAL_ERROR, "the overlay did not answer")))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fs,
+    io::{self, BufRead as _, BufReader, Write as _},
+    os::{
+        fd::{AsRawFd as _, RawFd},
+        unix::{
+            fs::{FileTypeExt as _, PermissionsExt as _},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::{Context as _, bail};
+use winit::event_loop::EventLoopProxy;
+
+use super::handle_message;
+use crate::event::OverlayEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Binds the socket at `path` and serves each client on its own thread
+/// until `stop` is set, then removes the socket.
+pub(super) fn spawn(
+    path: PathBuf,
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let listener = bind(&path)?;
+    listener
+        .set_nonblocking(true)
+        .context("make the control socket non-blocking")?;
+    thread::Builder::new()
+        .name("rs_overlay-control".to_owned())
+        .spawn(move || {
+            if let Err(err) = run(&listener, &proxy, &stop) {
+                let _ = proxy.send_event(OverlayEvent::Status(format!("control socket: {err:#}")));
+            }
+            let _ = fs::remove_file(&path);
+        })
+        .context("spawn control socket thread")
+}
+
+fn bind(path: &Path) -> anyhow::Result<UnixListener> {
+    if UnixStream::connect(path).is_ok() {
+        bail!("another overlay is already listening on {}", path.display());
+    }
+    // Left behind by an overlay that did not exit cleanly.
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = fs::remove_file(path);
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("listen on {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("restrict access to {}", path.display()))?;
+    Ok(listener)
+}
+
+fn run(
+    listener: &UnixListener,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    stop: &Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    while !stop.load(Ordering::Relaxed) {
+        wait_readable(listener.as_raw_fd(), POLL_INTERVAL);
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(err) => return Err(err).context("accept a control client"),
+        };
+        if !same_user(&stream) {
+            continue;
+        }
+        let proxy = proxy.clone();
+        let stop = stop.clone();
+        thread::Builder::new()
+            .name("rs_overlay-control-client".to_owned())
+            .spawn(move || {
+                let _ = serve(stream, &proxy, &stop);
+            })
+            .context("spawn control client thread")?;
+    }
+    Ok(())
+}
+
+/// Answers the client's requests, one per line, until it hangs up.
+fn serve(
+    stream: UnixStream,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        // A timeout leaves what was read so far in `line`.
+        let eof = match reader.read_until(b'\n', &mut line) {
+            Ok(read) => read == 0,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        if !line.trim_ascii().is_empty()
+            && let Some(response) = handle_message(&line, proxy)
+        {
+            let mut text = response.to_string();
+            text.push('\n');
+            writer.write_all(text.as_bytes())?;
+        }
+        line.clear();
+        if eof {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the process at the other end runs as the same user. The socket's
+/// mode already keeps others out, except in the moment between binding and
+/// changing it.
+fn same_user(stream: &UnixStream) -> bool {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+    let found = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&raw mut credentials).cast(),
+            &mut len,
+        )
+    } == 0;
+    found && credentials.uid == unsafe { libc::getuid() }
+}
+
+fn wait_readable(fd: RawFd, timeout: Duration) {
+    let mut fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    unsafe {
+        libc::poll(&mut fd, 1, timeout_ms);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{fs, path::Path};
+
+use anyhow::Context as _;
+use toml_edit::{Array, DocumentMut, Item, Value, value};
+
+use crate::Placement;
+
+/// Edge length of the resize handle on screen, in points.
+const HANDLE_SIZE: f32 = 10.0;
+/// Resizing snaps the scale to multiples of this.
+const SCALE_STEP: f32 = 0.05;
+const MIN_SCALE: f32 = 0.25;
+const MAX_SCALE: f32 = 8.0;
+const OUTLINE_COLOR: egui::Color32 = egui::Color32::from_rgb(0x4c, 0x9e, 0xff);
+
+/// Edit mode, where the widgets take the pointer: dragging one moves it,
+/// dragging its corner handle resizes it.
+pub(crate) struct EditSession {
+    grid: f32,
+    drag: Option<Drag>,
+    /// Widgets whose placement changed since it was last saved.
+    moved: Vec<usize>,
+    /// Where the window being drawn takes input, in points.
+    input: Vec<egui::Rect>,
+    done: bool,
+}
+
+/// Offset and scale as dragged so far, before snapping.
+struct Drag {
+    widget: usize,
+    kind: DragKind,
+    offset: egui::Vec2,
+    scale: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DragKind {
+    Move,
+    Resize,
+}
+
+impl EditSession {
+    pub(crate) fn new(grid: f32) -> Self {
+        Self {
+            grid,
+            drag: None,
+            moved: Vec::new(),
+            input: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.input.clear();
+    }
+
+    /// Whether the banner's "Done" button was clicked.
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub(crate) fn input(&self) -> &[egui::Rect] {
+        &self.input
+    }
+
+    /// Widgets moved or resized since the last call.
+    pub(crate) fn take_moved(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.moved)
+    }
+
+    /// Outlines the widget `ui` just painted and applies drags on it to
+    /// `placement`.
+    pub(crate) fn interact(&mut self, ui: &mut egui::Ui, widget: usize, placement: &mut Placement) {
+        let rect = ui.min_rect();
+        let direction = placement.anchor.direction();
+        // The handle sits on the corner away from the anchor, which stays put
+        // while the widget scales.
+        let corner = egui::pos2(
+            if direction.x < 0.0 {
+                rect.left()
+            } else {
+                rect.right()
+            },
+            if direction.y < 0.0 {
+                rect.top()
+            } else {
+                rect.bottom()
+            },
+        );
+        let handle_rect =
+            egui::Rect::from_center_size(corner, egui::Vec2::splat(HANDLE_SIZE / placement.scale));
+
+        let id = ui.id().with("rs_overlay_edit");
+        let body = ui.interact(rect, id.with("move"), egui::Sense::drag());
+        let handle = ui.interact(handle_rect, id.with("resize"), egui::Sense::drag());
+
+        for (response, kind) in [(&body, DragKind::Move), (&handle, DragKind::Resize)] {
+            if response.drag_started() {
+                self.drag = Some(Drag {
+                    widget,
+                    kind,
+                    offset: egui::Vec2::from(placement.offset),
+                    scale: placement.scale,
+                });
+            }
+        }
+        if let Some(drag) = &mut self.drag
+            && drag.widget == widget
+        {
+            // Screen points, unlike `Response::drag_delta` in a scaled layer.
+            let delta = ui.input(|input| input.pointer.delta());
+            match drag.kind {
+                DragKind::Move if body.dragged() => {
+                    drag.offset += delta * direction;
+                    placement.offset = snap_offset(drag.offset, self.grid).into();
+                }
+                DragKind::Resize if handle.dragged() => {
+                    drag.scale += delta.x * direction.x / rect.width().max(1.0);
+                    placement.scale = snap_scale(drag.scale);
+                }
+                _ => {}
+            }
+            if body.drag_stopped() || handle.drag_stopped() {
+                self.drag = None;
+                self.moved.push(widget);
+            }
+        }
+
+        if handle.hovered() || handle.dragged() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeNwSe);
+        } else if body.dragged() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+        } else if body.hovered() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Grab);
+        }
+        let stroke = egui::Stroke::new(1.0 / placement.scale, OUTLINE_COLOR);
+        ui.painter().rect_stroke(rect, 0.0, stroke);
+        ui.painter().rect_filled(handle_rect, 0.0, OUTLINE_COLOR);
+    }
+
+    /// Adds a widget covering `rect` on screen, and its handle, to the area
+    /// that takes input.
+    pub(crate) fn add_widget(&mut self, rect: egui::Rect) {
+        self.input.push(rect.expand(HANDLE_SIZE / 2.0));
+    }
+
+    /// Explains the mode and offers a way out.
+    pub(crate) fn paint_banner(&mut self, ctx: &egui::Context) {
+        let response = egui::Area::new(egui::Id::new("rs_overlay_edit_banner"))
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 12.0))
+            .order(egui::Order::Foreground)
+            .movable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Edit mode: drag a widget to move it, its handle to resize it");
+                        if ui.button("Done").clicked() {
+                            self.done = true;
+                        }
+                    });
+                });
+            });
+        self.input.push(response.response.rect);
+    }
+}
+
+/// Moves `rect`'s content `scale` times its size around the point `align`
+/// picks on it, as the layer transform of the widget's area.
+pub(crate) fn scale_transform(
+    align: egui::Align2,
+    rect: egui::Rect,
+    scale: f32,
+) -> egui::emath::TSTransform {
+    let pivot = align.pos_in_rect(&rect).to_vec2();
+    egui::emath::TSTransform::new(pivot * (1.0 - scale), scale)
+}
+
+fn snap_offset(offset: egui::Vec2, grid: f32) -> egui::Vec2 {
+    if grid <= 0.0 {
+        return offset;
+    }
+    (offset / grid).round() * grid
+}
+
+fn snap_scale(scale: f32) -> f32 {
+    ((scale / SCALE_STEP).round() * SCALE_STEP).clamp(MIN_SCALE, MAX_SCALE)
+}
+
+/// Writes `placements`, each paired with the index of its `[[widget]]`
+/// table, into the config file at `path`. Everything else in the file,
+/// comments included, stays as it is. Returns what was written.
+pub(crate) fn save_placements(
+    path: &Path,
+    placements: &[(usize, &Placement)],
+) -> anyhow::Result<String> {
+    let text = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let mut document: DocumentMut = text
+        .parse()
+        .with_context(|| format!("parse {}", path.display()))?;
+    let tables = document
+        .get_mut("widget")
+        .and_then(Item::as_array_of_tables_mut)
+        .with_context(|| {
+            format!(
+                "{} has no [[widget]] tables to save the positions in",
+                path.display()
+            )
+        })?;
+    for &(index, placement) in placements {
+        let table = tables
+            .get_mut(index)
+            .with_context(|| format!("{} lacks [[widget]] table {}", path.display(), index + 1))?;
+        table["offset"] = value(Array::from_iter(placement.offset.map(number)));
+        if placement.scale == 1.0 {
+            table.remove("zoom");
+        } else {
+            table["zoom"] = value(number(placement.scale));
+        }
+    }
+    let text = document.to_string();
+    fs::write(path, &text).with_context(|| format!("write {}", path.display()))?;
+    Ok(text)
+}
+
+/// Whole numbers stay integers, the way they are usually written by hand.
+fn number(number: f32) -> Value {
+    if number.fract() == 0.0 {
+        Value::from(number as i64)
+    } else {
+        Value::from(rounded(number))
+    }
+}
+
+fn rounded(number: f32) -> f64 {
+    (f64::from(number) * 100.0).round() / 100.0
+}
+
+/// `number` as it reads back from the file `save_placements` wrote.
+pub(crate) fn saved_number(number: f32) -> f32 {
+    if number.fract() == 0.0 {
+        number
+    } else {
+        rounded(number) as f32
+    }
+}
@@ -0,0 +1,39 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use crate::{config::HotkeyAction, control::ControlCall};
+
+/// Messages delivered to the overlay's event loop from background threads.
+#[derive(Debug)]
+pub(crate) enum OverlayEvent {
+    ConfigChanged,
+    /// Monitors were connected, disconnected or changed mode or scale.
+    MonitorsChanged,
+    /// The window the overlay follows moved, resized, or was mapped or
+    /// unmapped.
+    Target(TargetGeometry),
+    /// Whether the `[visibility]` rules currently show the widgets.
+    Visibility(bool),
+    /// Something worth telling the user, such as which window is followed.
+    Status(String),
+    /// Whether a compositing manager blends the overlay into the desktop.
+    Compositing(bool),
+    /// The layer-shell connection has events to dispatch.
+    Wayland,
+    /// Enter edit mode, or leave it.
+    ToggleEditMode,
+    /// A `[hotkeys]` binding was pressed.
+    Hotkey(HotkeyAction),
+    /// A control socket client's request, which has to be answered.
+    Control(ControlCall),
+}
+
+/// The client area of a followed window, in root window coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TargetGeometry {
+    /// Not found yet, unmapped, minimized or destroyed.
+    Hidden,
+    Visible {
+        position: PhysicalPosition<i32>,
+        size: PhysicalSize<u32>,
+    },
+}
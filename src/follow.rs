@@ -0,0 +1,46 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use winit::{event_loop::EventLoopProxy, window::Window};
+
+use crate::{config::WindowSelector, event::OverlayEvent};
+
+/// Keeps an overlay window on top of another application's window. Dropping
+/// it stops the background thread.
+pub(crate) struct WindowFollower {
+    stop: Arc<AtomicBool>,
+}
+
+impl WindowFollower {
+    pub(crate) fn start(
+        overlay: &Window,
+        selector: &WindowSelector,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Self> {
+        if selector.is_empty() {
+            anyhow::bail!("[window.follow] needs a pid, class or title");
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let Some(xid) = crate::x11::window_xid(overlay) else {
+                anyhow::bail!("following a window needs an X11 session");
+            };
+            let stop = Arc::new(AtomicBool::new(false));
+            crate::x11::spawn_window_follower(selector.clone(), xid, proxy, stop.clone())?;
+            Ok(Self { stop })
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            let _ = (overlay, proxy);
+            anyhow::bail!("following a window needs an X11 session")
+        }
+    }
+}
+
+impl Drop for WindowFollower {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on retained frames so a very high frame rate cannot grow the
+/// history without limit.
+const MAX_FRAMES: usize = 1 << 16;
+
+/// How far back frames are kept unless `with_history` says otherwise.
+pub const DEFAULT_HISTORY: Duration = Duration::from_secs(10);
+
+pub struct FpsTracker {
+    last_instant: Instant,
+    frame_count: u32,
+    fps: f32,
+    last_frame: Option<Instant>,
+    /// Ring buffer of (end of frame, frame duration), oldest first.
+    frames: VecDeque<(Instant, Duration)>,
+    history: Duration,
+}
+
+impl FpsTracker {
+    pub fn new() -> Self {
+        Self {
+            last_instant: Instant::now(),
+            frame_count: 0,
+            fps: 0.0,
+            last_frame: None,
+            frames: VecDeque::new(),
+            history: DEFAULT_HISTORY,
+        }
+    }
+
+    /// How far back per-frame durations are kept; bounds the largest window
+    /// `stats` can cover.
+    pub fn with_history(mut self, history: Duration) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn tick(&mut self) {
+        self.record(Instant::now());
+    }
+
+    /// Records a frame that finished at `at`, for frames timed somewhere other
+    /// than the overlay's own loop.
+    pub fn record(&mut self, at: Instant) {
+        if let Some(last) = self.last_frame {
+            let duration = at.saturating_duration_since(last);
+            if self.frames.len() == MAX_FRAMES {
+                self.frames.pop_front();
+            }
+            self.frames.push_back((at, duration));
+        }
+        self.last_frame = Some(at);
+        while let Some(&(end, _)) = self.frames.front() {
+            if at.saturating_duration_since(end) <= self.history {
+                break;
+            }
+            self.frames.pop_front();
+        }
+
+        self.frame_count = self.frame_count.saturating_add(1);
+        let elapsed = at.saturating_duration_since(self.last_instant);
+        if elapsed.as_secs_f32() >= 1.0 {
+            self.fps = self.frame_count as f32 / elapsed.as_secs_f32();
+            self.frame_count = 0;
+            self.last_instant = at;
+        }
+    }
+
+    /// Frames per second averaged over the last completed one-second window.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Per-frame durations, oldest first, as (end of frame, duration).
+    pub fn frame_times(&self) -> impl DoubleEndedIterator<Item = (Instant, Duration)> + '_ {
+        self.frames.iter().copied()
+    }
+
+    /// Statistics over frames that ended within `window` of the newest frame.
+    pub fn stats(&self, window: Duration) -> FrameStats {
+        let Some(&(newest, _)) = self.frames.back() else {
+            return FrameStats::default();
+        };
+        let mut times: Vec<f32> = self
+            .frames
+            .iter()
+            .rev()
+            .take_while(|(end, _)| newest.saturating_duration_since(*end) <= window)
+            .map(|(_, duration)| duration.as_secs_f32() * 1000.0)
+            .collect();
+        FrameStats::from_frame_times(&mut times)
+    }
+}
+
+impl Default for FpsTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Summary of frame times over a window. Durations are in milliseconds.
+///
+/// The 1% and 0.1% lows are the frame rate implied by the mean of the slowest
+/// 1% and 0.1% of frames, so a single long hitch still shows up in them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameStats {
+    pub frames: usize,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    pub mean_ms: f32,
+    pub std_dev_ms: f32,
+    pub p50_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    pub mean_fps: f32,
+    pub low_1_fps: f32,
+    pub low_0_1_fps: f32,
+}
+
+impl FrameStats {
+    /// Sorts `times` in place.
+    pub fn from_frame_times(times: &mut [f32]) -> Self {
+        if times.is_empty() {
+            return Self::default();
+        }
+        times.sort_by(f32::total_cmp);
+
+        let count = times.len() as f32;
+        let mean = times.iter().sum::<f32>() / count;
+        let variance = times.iter().map(|time| (time - mean).powi(2)).sum::<f32>() / count;
+
+        Self {
+            frames: times.len(),
+            min_ms: times[0],
+            max_ms: times[times.len() - 1],
+            mean_ms: mean,
+            std_dev_ms: variance.sqrt(),
+            p50_ms: percentile(times, 0.50),
+            p95_ms: percentile(times, 0.95),
+            p99_ms: percentile(times, 0.99),
+            mean_fps: to_fps(mean),
+            low_1_fps: to_fps(slowest_mean(times, 0.01)),
+            low_0_1_fps: to_fps(slowest_mean(times, 0.001)),
+        }
+    }
+}
+
+/// Nearest-rank percentile of ascending `sorted`.
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    let rank = (fraction * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Mean of the slowest `fraction` of ascending `sorted`, at least one frame.
+fn slowest_mean(sorted: &[f32], fraction: f32) -> f32 {
+    let count = ((sorted.len() as f32 * fraction).ceil() as usize).clamp(1, sorted.len());
+    let slowest = &sorted[sorted.len() - count..];
+    slowest.iter().sum::<f32>() / count as f32
+}
+
+fn to_fps(frame_ms: f32) -> f32 {
+    if frame_ms > 0.0 {
+        1000.0 / frame_ms
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(times: &[f32]) -> FrameStats {
+        FrameStats::from_frame_times(&mut times.to_vec())
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        // 1..=100 ms, shuffled so the sort is exercised too.
+        let times: Vec<f32> = (1..=100).map(|ms| ((ms * 37) % 100 + 1) as f32).collect();
+        let stats = stats(&times);
+        assert_eq!(stats.frames, 100);
+        assert_eq!((stats.min_ms, stats.max_ms), (1.0, 100.0));
+        assert_eq!(stats.p50_ms, 50.0);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.p99_ms, 99.0);
+    }
+
+    #[test]
+    fn percentiles_round_the_rank_up() {
+        let stats = stats(&[10.0, 20.0, 30.0]);
+        // Ranks ⌈1.5⌉ = 2 and ⌈2.97⌉ = 3.
+        assert_eq!(stats.p50_ms, 20.0);
+        assert_eq!(stats.p99_ms, 30.0);
+        assert_eq!(stats.mean_ms, 20.0);
+    }
+
+    #[test]
+    fn lows_average_the_slowest_frames() {
+        let mut times = vec![16.0; 989];
+        times.extend([50.0; 10]);
+        times.push(100.0);
+        let stats = stats(&times);
+        // The slowest 10 frames average 55 ms, the slowest one takes 100 ms.
+        assert!((stats.low_1_fps - 1000.0 / 55.0).abs() < 1e-3);
+        assert!((stats.low_0_1_fps - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn lows_take_at_least_one_frame() {
+        let stats = stats(&[10.0, 20.0, 40.0]);
+        assert_eq!(stats.low_1_fps, 25.0);
+        assert_eq!(stats.low_0_1_fps, 25.0);
+    }
+
+    #[test]
+    fn no_frames_give_empty_stats() {
+        assert_eq!(stats(&[]), FrameStats::default());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use rs_overlay_shm::{Api, PublisherInfo, Subscriber};
+
+use crate::{
+    config::{HookApi, HookSourceOptions},
+    source::{SourceEvent, instant_from_monotonic},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Polls between checks that the measured process is still running.
+const ALIVE_CHECK_POLLS: u32 = 20;
+
+/// Forwards presents published by the hooked process `options` selects until
+/// `stop` is set, switching to another match when that process exits.
+pub(crate) fn spawn_hook_reader(
+    options: HookSourceOptions,
+    events: Sender<SourceEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("rs_overlay-hook-frames".to_owned())
+        .spawn(move || run(&options, &events, &stop))
+        .context("spawn hook reader")
+}
+
+fn run(options: &HookSourceOptions, events: &Sender<SourceEvent>, stop: &AtomicBool) {
+    let mut waiting_reported = false;
+    let mut timestamps = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        let Some(mut subscriber) = select(options) else {
+            if !waiting_reported {
+                waiting_reported = true;
+                let _ = events.send(SourceEvent::Status(
+                    "waiting for a hooked process matching [source]".to_owned(),
+                ));
+            }
+            thread::sleep(SEARCH_INTERVAL);
+            continue;
+        };
+        waiting_reported = false;
+        let info = subscriber.info().clone();
+        let _ = events.send(SourceEvent::Status(format!(
+            "measuring {} (pid {}, {})",
+            info.app_name,
+            info.pid,
+            info.api.name()
+        )));
+
+        let mut polls = 0;
+        while !stop.load(Ordering::Relaxed) {
+            subscriber.read_new(&mut timestamps);
+            for timestamp in timestamps.drain(..) {
+                let at = instant_from_monotonic(Duration::from_nanos(timestamp));
+                let _ = events.send(SourceEvent::Frame(at));
+            }
+            polls += 1;
+            if polls % ALIVE_CHECK_POLLS == 0 && !info.is_alive() {
+                let _ = events.send(SourceEvent::Status(format!(
+                    "{} (pid {}) exited",
+                    info.app_name, info.pid
+                )));
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Opens the matching publisher that presented most recently.
+fn select(options: &HookSourceOptions) -> Option<Subscriber> {
+    rs_overlay_shm::discover()
+        .into_iter()
+        .filter(|info| matches(info, options))
+        .filter_map(|info| Subscriber::open(&info.path).ok())
+        .max_by_key(|subscriber| subscriber.latest())
+}
+
+fn matches(info: &PublisherInfo, options: &HookSourceOptions) -> bool {
+    options.pid.is_none_or(|pid| info.pid == pid)
+        && options
+            .app
+            .as_ref()
+            .is_none_or(|pattern| pattern.0.is_match(&info.app_name))
+        && options.api.is_none_or(|api| info.api == api.into())
+}
+
+impl From<HookApi> for Api {
+    fn from(api: HookApi) -> Self {
+        match api {
+            HookApi::Vulkan => Self::Vulkan,
+            HookApi::Gl => Self::Gl,
+        }
+    }
+}
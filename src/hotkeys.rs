@@ -0,0 +1,55 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+};
+
+use winit::event_loop::{EventLoopProxy, EventLoopWindowTarget};
+
+use crate::{config::HotkeysConfig, event::OverlayEvent};
+
+/// Reports presses of the `[hotkeys]` bindings as `OverlayEvent::Hotkey`.
+/// Dropping it waits for the background thread to release the keys, so a
+/// replacement can grab them again.
+pub(crate) struct HotkeyWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HotkeyWatcher {
+    /// `None` when no bindings are set. Grabbing keys globally needs X11.
+    pub(crate) fn start(
+        config: &HotkeysConfig,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        let bindings = config.bindings();
+        if bindings.is_empty() {
+            return Ok(None);
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if crate::x11::is_x11(target) {
+                let stop = Arc::new(AtomicBool::new(false));
+                let thread = crate::x11::spawn_hotkey_grabber(bindings, proxy, stop.clone())?;
+                return Ok(Some(Self {
+                    stop,
+                    thread: Some(thread),
+                }));
+            }
+        }
+        let _ = (target, proxy);
+        anyhow::bail!("[hotkeys] need an X11 session")
+    }
+}
+
+impl Drop for HotkeyWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
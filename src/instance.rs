@@ -0,0 +1,130 @@
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{self, Read as _},
+    os::{
+        fd::AsRawFd as _,
+        unix::{
+            ffi::OsStringExt as _,
+            fs::{FileExt as _, MetadataExt as _, OpenOptionsExt as _},
+        },
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+
+/// How long a second overlay waits for a starting one to record where its
+/// control socket is.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+const PUBLISH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held by the overlay running for this user; a second one started without
+/// `--new-instance` hands its arguments over instead of opening windows.
+/// The kernel releases the lock when the process exits, however it does.
+///
+/// The locked file holds the path of the holder's control socket and a
+/// newline, only the newline while it has none, and nothing until it has
+/// started the socket, so the second overlay reaches it whatever its own
+/// config says.
+pub struct InstanceLock {
+    file: File,
+}
+
+impl InstanceLock {
+    /// `None` when another overlay holds the lock.
+    pub fn acquire() -> anyhow::Result<Option<Self>> {
+        let path = lock_path();
+        let file = open_owned(
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .mode(0o600),
+            &path,
+        )?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(err).with_context(|| format!("lock {}", path.display()));
+        }
+        // What an overlay that exited wrote no longer holds.
+        file.set_len(0)
+            .with_context(|| format!("write {}", path.display()))?;
+        Ok(Some(Self { file }))
+    }
+
+    /// Records the control socket the overlay answers on once it listens,
+    /// if any.
+    pub(crate) fn publish(&self, socket: Option<&Path>) -> io::Result<()> {
+        let mut contents = match socket {
+            Some(socket) => std::path::absolute(socket)?.into_os_string().into_vec(),
+            None => Vec::new(),
+        };
+        contents.push(b'\n');
+        // Overwriting first and truncating after, readers see the new line
+        // whole, if perhaps followed by the rest of the old one.
+        self.file.write_all_at(&contents, 0)?;
+        self.file.set_len(contents.len() as u64)
+    }
+
+    /// The control socket of the overlay holding the lock, `None` when it
+    /// has none. Waits a little for an overlay that is still starting.
+    pub fn running_socket() -> anyhow::Result<Option<PathBuf>> {
+        let path = lock_path();
+        let deadline = Instant::now() + PUBLISH_TIMEOUT;
+        loop {
+            let mut contents = Vec::new();
+            open_owned(OpenOptions::new().read(true), &path)?
+                .read_to_end(&mut contents)
+                .with_context(|| format!("read {}", path.display()))?;
+            if let Some(end) = contents.iter().position(|&byte| byte == b'\n') {
+                contents.truncate(end);
+                return Ok(Some(contents)
+                    .filter(|socket| !socket.is_empty())
+                    .map(|socket| PathBuf::from(OsString::from_vec(socket))));
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "the running overlay did not record its control socket in {}",
+                    path.display()
+                );
+            }
+            thread::sleep(PUBLISH_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Opens the lock file at `path`, which is predictable without
+/// `XDG_RUNTIME_DIR`: it is not followed when a symlink, and has to be a
+/// regular file of the user's own, so another user cannot plant one that
+/// points elsewhere.
+fn open_owned(options: &mut OpenOptions, path: &Path) -> anyhow::Result<File> {
+    let file = options
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .with_context(|| format!("open {}", path.display()))?;
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("stat {}", path.display()))?;
+    if !metadata.is_file() || metadata.uid() != unsafe { libc::getuid() } {
+        anyhow::bail!("{} is not a file of this user's own", path.display());
+    }
+    Ok(file)
+}
+
+/// `$XDG_RUNTIME_DIR/rs_overlay.lock`, or a per-user file in the temporary
+/// directory without one.
+fn lock_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("rs_overlay.lock"),
+        None => {
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("rs_overlay-{uid}.lock"))
+        }
+    }
+}
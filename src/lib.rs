@@ -0,0 +1,43 @@
+mod benchmark;
+mod compositor;
+pub mod config;
+pub mod control;
+mod edit;
+mod event;
+mod follow;
+pub mod fps;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod hook;
+mod hotkeys;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod instance;
+pub mod metrics;
+mod monitors;
+mod overlay;
+mod platform;
+mod reload;
+mod render;
+mod schedule;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod signal;
+pub mod snapshot;
+mod source;
+mod toast;
+mod visibility;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod wayland;
+pub mod widget;
+pub mod widgets;
+#[cfg(windows)]
+mod windows;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11;
+
+pub use config::Config;
+pub use fps::FpsTracker;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub use instance::InstanceLock;
+pub use metrics::Metrics;
+pub use monitors::{MonitorGeometry, MonitorInfo, list_monitors};
+pub use overlay::Overlay;
+pub use widget::{Placement, Widget, WidgetContext, WidgetStyle};
@@ -1,297 +1,349 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    iter::Peekable,
+    path::{Path, PathBuf},
+};
 
-mod platform;
-#[cfg(windows)]
-mod windows;
+use anyhow::{Context as _, bail};
+use rs_overlay::{Config, FpsTracker, Overlay};
 
-use winit::{
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::{WindowBuilder, WindowLevel},
-};
+const USAGE: &str = "\
+usage: rs_overlay [run] [--config <path>] [--layout <name>] [--show | --hide] [--new-instance]
+       rs_overlay [run] [--config <path>] \
+[--screenshot <png> [--size <width>x<height>] [--scale <factor>]]
+       rs_overlay ctl [--config <path>] [--json] <command>
+       rs_overlay list-monitors
 
-#[cfg(windows)]
-use winit::platform::windows::WindowBuilderExtWindows;
+ctl commands:
+  show [<widget>]          show a widget hidden by `hide`, or the overlay
+  hide [<widget>]          hide a widget, or the whole overlay
+  set <widget> <text>...   replace what a text widget shows
+  push <metric> <value>... add samples to a metric
+  layout [<name>]          switch layouts, or list them
+  stats [<window_ms>]      frame statistics of the running overlay
 
-struct RenderState {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
-    config: wgpu::SurfaceConfiguration,
-}
+Widgets are picked by their `id` or their position among the widgets.
 
-struct EguiState {
-    ctx: egui::Context,
-    state: egui_winit::State,
-    renderer: egui_wgpu::Renderer,
-}
+While an overlay runs, `run` hands --config, --layout, --show and --hide to
+it and exits, unless --new-instance is given.";
 
-struct FpsTracker {
-    last_instant: Instant,
-    frame_count: u32,
-    fps: f32,
-}
+type Args = Peekable<std::iter::Skip<std::env::ArgsOs>>;
 
-impl FpsTracker {
-    fn new() -> Self {
-        Self {
-            last_instant: Instant::now(),
-            frame_count: 0,
-            fps: 0.0,
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args_os().skip(1).peekable();
+    match args.peek().and_then(|arg| arg.to_str()) {
+        Some("run") => {
+            args.next();
+            run(args)
+        }
+        Some("ctl") => {
+            args.next();
+            ctl(args)
         }
+        Some("list-monitors") => {
+            args.next();
+            list_monitors(args)
+        }
+        _ => run(args),
     }
+}
 
-    fn tick(&mut self) {
-        self.frame_count = self.frame_count.saturating_add(1);
-        let elapsed = self.last_instant.elapsed();
-        if elapsed.as_secs_f32() >= 1.0 {
-            self.fps = self.frame_count as f32 / elapsed.as_secs_f32();
-            self.frame_count = 0;
-            self.last_instant = Instant::now();
+fn run(mut args: Args) -> anyhow::Result<()> {
+    let mut config_path: Option<PathBuf> = None;
+    let mut screenshot: Option<PathBuf> = None;
+    let mut size = [1280, 720];
+    let mut scale = 1.0;
+    let mut layout: Option<String> = None;
+    let mut shown: Option<bool> = None;
+    let mut new_instance = false;
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--config" | "-c") => {
+                config_path = Some(args.next().context("--config needs a path")?.into());
+            }
+            Some("--layout") => {
+                let value = args.next().context("--layout needs a name")?;
+                layout = Some(
+                    value
+                        .into_string()
+                        .map_err(|value| anyhow::anyhow!("invalid --layout {value:?}"))?,
+                );
+            }
+            Some("--show") => shown = Some(true),
+            Some("--hide") => shown = Some(false),
+            Some("--new-instance") => new_instance = true,
+            Some("--screenshot") => {
+                screenshot = Some(args.next().context("--screenshot needs a path")?.into());
+            }
+            Some("--size") => {
+                let value = args.next().context("--size needs <width>x<height>")?;
+                size = parse_size(value.to_str().unwrap_or_default())
+                    .with_context(|| format!("invalid --size {value:?}"))?;
+            }
+            Some("--scale") => {
+                let value = args.next().context("--scale needs a factor")?;
+                scale = value
+                    .to_str()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .filter(|scale| *scale > 0.0)
+                    .with_context(|| format!("invalid --scale {value:?}"))?;
+            }
+            Some("--help" | "-h") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
         }
     }
 
-    fn fps(&self) -> f32 {
-        self.fps
-    }
-}
+    let forwarded_config = config_path.clone();
+    let config_path = config_path.or_else(default_config_path);
+    let config = load_config(config_path.as_deref())?;
 
-fn main() {
-    #[cfg(windows)]
-    {
-        windows::run().expect("windows overlay failed");
-        return;
+    if let Some(output) = screenshot {
+        let snapshot = Overlay::from_config(&config).snapshot(&FpsTracker::new(), size, scale);
+        return snapshot.save_png(&output);
     }
 
-    pollster::block_on(run());
-}
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let lock = match new_instance {
+        true => None,
+        false => match rs_overlay::InstanceLock::acquire() {
+            Ok(Some(lock)) => Some(lock),
+            Ok(None) => return forward(forwarded_config, layout, shown),
+            Err(err) => {
+                eprintln!("warning: {err:#}; not checking for a running overlay");
+                None
+            }
+        },
+    };
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    let _ = (new_instance, forwarded_config);
 
-async fn run() {
-    let event_loop = EventLoop::new().expect("create event loop");
-    let mut builder = WindowBuilder::new();
-    builder = builder
-        .with_title("rs_overlay")
-        .with_decorations(false)
-        .with_resizable(false)
-        .with_transparent(true)
-        .with_window_level(WindowLevel::AlwaysOnTop);
-    #[cfg(windows)]
-    {
-        builder = builder
-            .with_no_redirection_bitmap(true)
-            .with_skip_taskbar(true);
+    let mut overlay = Overlay::from_config(&config).with_hidden(shown == Some(false));
+    if let Some(layout) = layout {
+        overlay = overlay.with_layout(layout);
     }
-    let window = Arc::new(builder.build(&event_loop).expect("create window"));
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(lock) = lock {
+        overlay = overlay.with_instance_lock(lock);
+    }
+    match config_path {
+        Some(path) => overlay.watch_config(path).run(),
+        None => overlay.run(),
+    }
+}
 
-    platform::configure_overlay(&window);
+/// Hands the arguments of a second `run` to the overlay already running.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn forward(
+    config_path: Option<PathBuf>,
+    layout: Option<String>,
+    shown: Option<bool>,
+) -> anyhow::Result<()> {
+    use rs_overlay::control::{ControlClient, ControlRequest};
 
-    if let Some(monitor) = window.primary_monitor() {
-        let position: PhysicalPosition<i32> = monitor.position();
-        let size: PhysicalSize<u32> = monitor.size();
-        window.as_ref().set_outer_position(position);
-        let _ = window.as_ref().request_inner_size(size);
+    let mut requests = Vec::new();
+    if let Some(path) = config_path {
+        // The running overlay has a working directory of its own.
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("resolve {}", path.display()))?;
+        requests.push(ControlRequest::LoadConfig { path });
+    }
+    if let Some(name) = layout {
+        requests.push(ControlRequest::Layout { name: Some(name) });
+    }
+    match shown {
+        Some(true) => requests.push(ControlRequest::Show { widget: None }),
+        Some(false) => requests.push(ControlRequest::Hide { widget: None }),
+        None => {}
+    }
+    if requests.is_empty() {
+        eprintln!("rs_overlay is already running; pass --new-instance to start another");
+        return Ok(());
     }
 
-    let size = window.inner_size();
-    let instance = wgpu::Instance::default();
-    let surface = instance
-        .create_surface(window.clone())
-        .expect("create surface");
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            compatible_surface: Some(&surface),
-            ..Default::default()
-        })
-        .await
-        .expect("find adapter");
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-            },
-            None,
-        )
-        .await
-        .expect("create device");
-    let surface_caps = surface.get_capabilities(&adapter);
-    let surface_format = surface_caps.formats[0];
-    let surface_alpha_mode = pick_alpha_mode(&surface_caps.alpha_modes);
-    let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: surface_format,
-        width: size.width.max(1),
-        height: size.height.max(1),
-        present_mode: wgpu::PresentMode::Fifo,
-        alpha_mode: surface_alpha_mode,
-        view_formats: vec![],
-        desired_maximum_frame_latency: 2,
-    };
-    surface.configure(&device, &config);
+    let unreachable =
+        "rs_overlay is already running but cannot be reached; pass --new-instance to start another";
+    let socket = rs_overlay::InstanceLock::running_socket()
+        .context(unreachable)?
+        .context(
+            "rs_overlay is already running without [control] enabled, so it cannot be reached; \
+             pass --new-instance to start another",
+        )?;
+    let mut client = ControlClient::connect_path(&socket).context(unreachable)?;
+    for request in &requests {
+        client.call(request)?;
+    }
+    Ok(())
+}
 
-    let ctx = egui::Context::default();
-    let state =
-        egui_winit::State::new(ctx.clone(), egui::ViewportId::ROOT, &event_loop, None, None);
-    let renderer = egui_wgpu::Renderer::new(&device, surface_format, None, 1);
+#[cfg(unix)]
+fn ctl(mut args: Args) -> anyhow::Result<()> {
+    use rs_overlay::control::{ControlClient, ControlRequest, WidgetRef};
 
-    let mut render_state = RenderState {
-        device,
-        queue,
-        surface,
-        config,
-    };
-    let mut egui_state = EguiState {
-        ctx,
-        state,
-        renderer,
+    let mut config_path: Option<PathBuf> = None;
+    let mut json = false;
+    loop {
+        match args.peek().and_then(|arg| arg.to_str()) {
+            Some("--config" | "-c") => {
+                args.next();
+                config_path = Some(args.next().context("--config needs a path")?.into());
+            }
+            Some("--json") => {
+                args.next();
+                json = true;
+            }
+            Some("--help" | "-h") => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => break,
+        }
+    }
+    let command = args.next().context(USAGE)?;
+    let mut args = args.map(|arg| {
+        arg.into_string()
+            .map_err(|arg| anyhow::anyhow!("argument {arg:?} is not valid UTF-8"))
+    });
+    let widget = |arg: String| match arg.parse() {
+        Ok(index) => WidgetRef::Index(index),
+        Err(_) => WidgetRef::Id(arg),
     };
-    let mut fps_tracker = FpsTracker::new();
-
-    let _ = event_loop.run(move |event, target| {
-        target.set_control_flow(ControlFlow::Poll);
-
-        match event {
-            Event::WindowEvent { event, window_id } if window_id == window.id() => {
-                match event {
-                    WindowEvent::CloseRequested => {
-                        target.exit();
-                        return;
-                    }
-                    WindowEvent::Resized(size) => {
-                        if size.width > 0 && size.height > 0 {
-                            render_state.config.width = size.width;
-                            render_state.config.height = size.height;
-                            render_state
-                                .surface
-                                .configure(&render_state.device, &render_state.config);
-                        }
-                    }
-                    WindowEvent::RedrawRequested => {
-                        fps_tracker.tick();
-
-                        let raw_input = egui_state.state.take_egui_input(&window);
-                        let full_output = egui_state.ctx.run(raw_input, |ctx| {
-                            let fps_text = format!("FPS: {:.1}", fps_tracker.fps());
-                            egui::Area::new(egui::Id::new("fps_overlay"))
-                                .fixed_pos(egui::pos2(12.0, 12.0))
-                                .show(ctx, |ui| {
-                                    egui::Frame::none().show(ui, |ui| {
-                                        ui.label(egui::RichText::new(fps_text).size(18.0));
-                                    });
-                                });
-                        });
 
-                        egui_state
-                            .state
-                            .handle_platform_output(&window, full_output.platform_output);
-
-                        let paint_jobs = egui_state
-                            .ctx
-                            .tessellate(full_output.shapes, full_output.pixels_per_point);
-                        let screen_descriptor = egui_wgpu::ScreenDescriptor {
-                            size_in_pixels: [render_state.config.width, render_state.config.height],
-                            pixels_per_point: egui_state.ctx.pixels_per_point(),
-                        };
-
-                        for (id, image_delta) in &full_output.textures_delta.set {
-                            egui_state.renderer.update_texture(
-                                &render_state.device,
-                                &render_state.queue,
-                                *id,
-                                image_delta,
-                            );
-                        }
-
-                        for id in &full_output.textures_delta.free {
-                            egui_state.renderer.free_texture(id);
-                        }
+    let request = match command.to_str() {
+        Some("show") => ControlRequest::Show {
+            widget: args.next().transpose()?.map(widget),
+        },
+        Some("hide") => ControlRequest::Hide {
+            widget: args.next().transpose()?.map(widget),
+        },
+        Some("set") => ControlRequest::SetText {
+            widget: widget(args.next().context("set needs a widget")??),
+            text: args.collect::<anyhow::Result<Vec<_>>>()?.join(" "),
+        },
+        Some("push") => {
+            let metric = args.next().context("push needs a metric")??;
+            let values = args
+                .map(|value| {
+                    let value = value?;
+                    value
+                        .parse()
+                        .with_context(|| format!("invalid sample {value:?}"))
+                })
+                .collect::<anyhow::Result<Vec<f32>>>()?;
+            if values.is_empty() {
+                bail!("push needs at least one value");
+            }
+            ControlRequest::Push { metric, values }
+        }
+        Some("layout") => ControlRequest::Layout {
+            name: args.next().transpose()?,
+        },
+        Some("stats") => ControlRequest::Stats {
+            window_ms: args
+                .next()
+                .transpose()?
+                .map(|window| {
+                    window
+                        .parse()
+                        .with_context(|| format!("invalid window {window:?}"))
+                })
+                .transpose()?,
+        },
+        _ => bail!("unknown ctl command {command:?}\n{USAGE}"),
+    };
 
-                        let output_frame = match render_state.surface.get_current_texture() {
-                            Ok(frame) => frame,
-                            Err(wgpu::SurfaceError::Lost) => {
-                                render_state
-                                    .surface
-                                    .configure(&render_state.device, &render_state.config);
-                                return;
-                            }
-                            Err(wgpu::SurfaceError::OutOfMemory) => {
-                                target.exit();
-                                return;
-                            }
-                            Err(_) => return,
-                        };
-                        let view = output_frame
-                            .texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-                        let mut encoder = render_state.device.create_command_encoder(
-                            &wgpu::CommandEncoderDescriptor { label: None },
-                        );
-                        egui_state.renderer.update_buffers(
-                            &render_state.device,
-                            &render_state.queue,
-                            &mut encoder,
-                            &paint_jobs,
-                            &screen_descriptor,
-                        );
+    let config = load_config(config_path.or_else(default_config_path).as_deref())?;
+    let mut client = ControlClient::connect(&config.control)?;
+    let result = client.call(&request)?;
+    if json {
+        println!("{result}");
+        return Ok(());
+    }
+    match request {
+        ControlRequest::Layout { name: None } => print_layouts(&result),
+        ControlRequest::Stats { .. } => print_stats(&result)?,
+        _ => {}
+    }
+    Ok(())
+}
 
-                        {
-                            let mut rpass =
-                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                    label: None,
-                                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                        view: &view,
-                                        resolve_target: None,
-                                        ops: wgpu::Operations {
-                                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                                r: 0.0,
-                                                g: 0.0,
-                                                b: 0.0,
-                                                a: 0.0,
-                                            }),
-                                            store: wgpu::StoreOp::Store,
-                                        },
-                                    })],
-                                    depth_stencil_attachment: None,
-                                    occlusion_query_set: None,
-                                    timestamp_writes: None,
-                                });
+#[cfg(not(unix))]
+fn ctl(_args: Args) -> anyhow::Result<()> {
+    bail!("ctl needs a Unix system")
+}
 
-                            egui_state
-                                .renderer
-                                .render(&mut rpass, &paint_jobs, &screen_descriptor);
-                        }
+/// One layout per line, the current one marked.
+#[cfg(unix)]
+fn print_layouts(result: &serde_json::Value) {
+    let current = result["layout"].as_str();
+    for layout in result["layouts"].as_array().into_iter().flatten() {
+        let Some(layout) = layout.as_str() else {
+            continue;
+        };
+        let marker = if Some(layout) == current { '*' } else { ' ' };
+        println!("{marker} {layout}");
+    }
+}
 
-                        render_state.queue.submit(Some(encoder.finish()));
-                        output_frame.present();
-                    }
-                    _ => {}
-                }
+#[cfg(unix)]
+fn print_stats(result: &serde_json::Value) -> anyhow::Result<()> {
+    let stats: rs_overlay::fps::FrameStats =
+        serde_json::from_value(result["stats"].clone()).context("decode stats")?;
+    println!("fps: {:.1}", result["fps"].as_f64().unwrap_or_default());
+    println!("frames: {}", stats.frames);
+    println!("min: {:.2} ms", stats.min_ms);
+    println!("max: {:.2} ms", stats.max_ms);
+    println!("mean: {:.2} ms", stats.mean_ms);
+    println!("σ: {:.2} ms", stats.std_dev_ms);
+    println!("p50: {:.2} ms", stats.p50_ms);
+    println!("p95: {:.2} ms", stats.p95_ms);
+    println!("p99: {:.2} ms", stats.p99_ms);
+    println!("1% low: {:.1}", stats.low_1_fps);
+    println!("0.1% low: {:.1}", stats.low_0_1_fps);
+    Ok(())
+}
 
-                let response = egui_state.state.on_window_event(&window, &event);
-                if response.repaint {
-                    window.request_redraw();
-                }
-            }
-            Event::AboutToWait => {
-                window.request_redraw();
-            }
-            _ => {}
+fn list_monitors(mut args: Args) -> anyhow::Result<()> {
+    if let Some(arg) = args.next() {
+        bail!("unexpected argument {arg:?}\n{USAGE}");
+    }
+    for monitor in rs_overlay::list_monitors()? {
+        let mut line = format!(
+            "{}\t{}",
+            monitor.index,
+            monitor.name.as_deref().unwrap_or("(unnamed)")
+        );
+        if let Some(geometry) = monitor.geometry {
+            let [x, y] = geometry.position;
+            let [width, height] = geometry.size;
+            line += &format!(
+                "\t{width}x{height}+{x}+{y}\tscale {}",
+                geometry.scale_factor
+            );
         }
-    });
+        if monitor.primary {
+            line += "\tprimary";
+        }
+        println!("{line}");
+    }
+    Ok(())
 }
 
-fn pick_alpha_mode(modes: &[wgpu::CompositeAlphaMode]) -> wgpu::CompositeAlphaMode {
-    let preferred = [
-        wgpu::CompositeAlphaMode::PreMultiplied,
-        wgpu::CompositeAlphaMode::PostMultiplied,
-        wgpu::CompositeAlphaMode::Inherit,
-        wgpu::CompositeAlphaMode::Opaque,
-    ];
+fn default_config_path() -> Option<PathBuf> {
+    Config::default_path().filter(|path| path.exists())
+}
+
+fn load_config(path: Option<&Path>) -> anyhow::Result<Config> {
+    Ok(match path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    })
+}
 
-    preferred
-        .into_iter()
-        .find(|mode| modes.contains(mode))
-        .unwrap_or(wgpu::CompositeAlphaMode::Opaque)
+fn parse_size(value: &str) -> Option<[u32; 2]> {
+    let (width, height) = value.split_once('x')?;
+    let size = [width.parse().ok()?, height.parse().ok()?];
+    size.iter().all(|side| *side > 0).then_some(size)
 }
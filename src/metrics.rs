@@ -0,0 +1,36 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+/// Samples kept per metric; older ones are dropped.
+const MAX_SAMPLES: usize = 4096;
+
+/// Values pushed from outside the overlay, such as a game server's tick
+/// time, by metric name.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    series: HashMap<String, VecDeque<(Instant, f32)>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, name: &str, value: f32, at: Instant) {
+        let samples = match self.series.get_mut(name) {
+            Some(samples) => samples,
+            None => self.series.entry(name.to_owned()).or_default(),
+        };
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back((at, value));
+    }
+
+    /// Samples of `name`, oldest first, as (time pushed, value).
+    pub fn samples(&self, name: &str) -> impl DoubleEndedIterator<Item = (Instant, f32)> + '_ {
+        self.series.get(name).into_iter().flatten().copied()
+    }
+}
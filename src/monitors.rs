@@ -0,0 +1,181 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Context as _;
+use winit::{
+    event_loop::{EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget},
+    monitor::MonitorHandle,
+};
+
+use crate::{config::MonitorSelector, event::OverlayEvent};
+
+/// A monitor as whatever the overlay draws on knows it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Monitor {
+    Winit(MonitorHandle),
+    /// An output of the layer-shell connection.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    Output(crate::wayland::Output),
+}
+
+impl Monitor {
+    pub(crate) fn name(&self) -> Option<String> {
+        match self {
+            Self::Winit(handle) => handle.name(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Output(output) => output.name().map(str::to_owned),
+        }
+    }
+
+    /// `None` for outputs, which the compositor places surfaces on itself.
+    pub(crate) fn handle(&self) -> Option<&MonitorHandle> {
+        match self {
+            Self::Winit(handle) => Some(handle),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Output(_) => None,
+        }
+    }
+}
+
+/// A monitor as `rs_overlay list-monitors` shows it, to write `monitor`
+/// selectors against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// What `monitor = <index>` selects.
+    pub index: usize,
+    pub name: Option<String>,
+    pub primary: bool,
+    /// Unknown for layer-shell outputs, which the compositor places.
+    pub geometry: Option<MonitorGeometry>,
+}
+
+/// Position and size in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorGeometry {
+    pub position: [i32; 2],
+    pub size: [u32; 2],
+    pub scale_factor: f64,
+}
+
+/// The monitors the overlay would pick from, in the order `monitor`
+/// indices count them.
+pub fn list_monitors() -> anyhow::Result<Vec<MonitorInfo>> {
+    let event_loop = EventLoopBuilder::<OverlayEvent>::with_user_event()
+        .build()
+        .context("create event loop")?;
+    // The overlay falls back to winit windows without layer-shell, too.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Ok(Some(layer_shell)) =
+        crate::wayland::LayerShell::connect(&event_loop, event_loop.create_proxy())
+    {
+        return Ok(layer_shell.monitors().describe());
+    }
+    Ok(Monitors::query(&event_loop).describe())
+}
+
+/// The monitors connected when the overlay (re)opens its windows.
+pub(crate) struct Monitors {
+    available: Vec<Monitor>,
+    primary: Option<Monitor>,
+}
+
+impl Monitors {
+    pub(crate) fn new(available: Vec<Monitor>, primary: Option<Monitor>) -> Self {
+        Self { available, primary }
+    }
+
+    pub(crate) fn query(target: &EventLoopWindowTarget<OverlayEvent>) -> Self {
+        Self::new(
+            target.available_monitors().map(Monitor::Winit).collect(),
+            target.primary_monitor().map(Monitor::Winit),
+        )
+    }
+
+    /// Not every platform has a primary monitor; the first one stands in.
+    fn primary(&self) -> Option<&Monitor> {
+        self.primary.as_ref().or_else(|| self.available.first())
+    }
+
+    pub(crate) fn matches(&self, selector: &MonitorSelector, monitor: &Monitor) -> bool {
+        match selector {
+            MonitorSelector::Primary => self.primary() == Some(monitor),
+            MonitorSelector::All => true,
+            MonitorSelector::Index(index) => self.available.get(*index) == Some(monitor),
+            MonitorSelector::Name(name) => monitor.name().as_deref() == Some(name.as_str()),
+        }
+    }
+
+    fn describe(&self) -> Vec<MonitorInfo> {
+        let primary = self.primary();
+        self.available
+            .iter()
+            .enumerate()
+            .map(|(index, monitor)| MonitorInfo {
+                index,
+                name: monitor.name(),
+                primary: primary == Some(monitor),
+                geometry: monitor.handle().map(|handle| {
+                    let position = handle.position();
+                    let size = handle.size();
+                    MonitorGeometry {
+                        position: [position.x, position.y],
+                        size: [size.width, size.height],
+                        scale_factor: handle.scale_factor(),
+                    }
+                }),
+            })
+            .collect()
+    }
+
+    /// One entry per window to open, in the order of `selectors` and without
+    /// duplicates. Selectors that match nothing fall back to the primary
+    /// monitor, so there is always at least one window.
+    pub(crate) fn targets(&self, selectors: &[MonitorSelector]) -> Vec<Option<Monitor>> {
+        let mut targets = Vec::new();
+        for selector in selectors {
+            for monitor in &self.available {
+                let target = Some(monitor.clone());
+                if self.matches(selector, monitor) && !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        if targets.is_empty() {
+            targets.push(self.primary().cloned());
+        }
+        targets
+    }
+}
+
+/// Reports monitor changes winit has no event for. Dropping it stops the
+/// background thread.
+pub(crate) struct MonitorWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl MonitorWatcher {
+    /// `None` off X11, where there is nothing to watch.
+    pub(crate) fn start(
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if crate::x11::is_x11(target) {
+                let stop = Arc::new(AtomicBool::new(false));
+                crate::x11::spawn_monitor_watcher(proxy, stop.clone())?;
+                return Ok(Some(Self { stop }));
+            }
+        }
+        let _ = (target, proxy);
+        Ok(None)
+    }
+}
+
+impl Drop for MonitorWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,1887 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use serde_json::{Value, json};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{Event, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
+};
+
+#[cfg(windows)]
+use winit::platform::windows::WindowBuilderExtWindows;
+#[cfg(all(unix, not(target_os = "macos")))]
+use winit::platform::x11::{WindowBuilderExtX11, XWindowType};
+
+use crate::{
+    Config, FpsTracker, Metrics, Placement, Widget, WidgetContext,
+    benchmark::Benchmark,
+    compositor::CompositorWatcher,
+    config::{
+        BenchmarkConfig, ControlConfig, EditConfig, HotkeyAction, HotkeysConfig, MonitorSelector,
+        RendererConfig, SourceConfig, ThemeConfig, VisibilityConfig, WidgetConfig, WindowConfig,
+    },
+    control::{ControlCall, ControlRequest, ControlServer, RpcError, WidgetRef},
+    edit::{self, EditSession},
+    event::{OverlayEvent, TargetGeometry},
+    follow::WindowFollower,
+    fps,
+    hotkeys::HotkeyWatcher,
+    monitors::{Monitor, MonitorWatcher, Monitors},
+    platform,
+    reload::ConfigWatcher,
+    render::{Canvas, Rasterizer, RenderTarget, Renderer},
+    schedule::RedrawSchedule,
+    snapshot::Snapshot,
+    source::FrameSource,
+    toast::{ToastKind, Toasts},
+    visibility::{Fade, VisibilityWatcher},
+    widgets,
+};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::{
+    InstanceLock,
+    config::WindowLevel,
+    signal::SignalWatcher,
+    wayland::{LayerEvent, LayerShell, LayerWindow},
+    x11::{StackKeeper, WindowShaper},
+};
+
+const ERROR_TOAST_DURATION: Duration = Duration::from_secs(8);
+const STATUS_TOAST_DURATION: Duration = Duration::from_secs(4);
+/// Redraw interval while widgets fade in or out.
+const FADE_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+/// Span the control socket's `stats` cover unless the client picks one.
+const STATS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Layout passes a snapshot may take before it is drawn regardless.
+const SNAPSHOT_MAX_PASSES: usize = 4;
+
+struct WidgetSlot {
+    placement: Placement,
+    last_update: Option<Instant>,
+    /// The `[[widget]]` table the widget was built from. Only these widgets
+    /// are replaced when the file is reloaded, and only if their table
+    /// changed.
+    config: Option<WidgetConfig>,
+    /// The config's `id`, for the control socket.
+    id: Option<String>,
+    /// The config's `type`, or `custom` for widgets added in code.
+    kind: &'static str,
+    /// Set when hidden over the control socket.
+    hidden: bool,
+    widget: Box<dyn Widget>,
+}
+
+impl WidgetSlot {
+    fn refresh_interval(&self) -> Duration {
+        self.placement
+            .refresh
+            .unwrap_or_else(|| self.widget.refresh_interval())
+    }
+
+    /// `None` until the first update.
+    fn next_update(&self) -> Option<Instant> {
+        self.last_update.map(|last| last + self.refresh_interval())
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.next_update().is_none_or(|next| now >= next)
+    }
+}
+
+/// Builder for transparent, click-through overlay windows, one per selected
+/// monitor, and the widgets drawn on them.
+pub struct Overlay {
+    window: WindowConfig,
+    renderer: RendererConfig,
+    source: SourceConfig,
+    visibility: VisibilityConfig,
+    edit: EditConfig,
+    hotkeys: HotkeysConfig,
+    benchmark: BenchmarkConfig,
+    control: ControlConfig,
+    /// What the widgets from the config file were styled with.
+    theme: ThemeConfig,
+    widgets: Vec<WidgetSlot>,
+    config_path: Option<PathBuf>,
+    /// The layout to start with, instead of the first one.
+    layout: Option<String>,
+    /// Whether to start with the widgets hidden.
+    hidden: bool,
+    /// Set when this is the overlay later ones hand their arguments to.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    instance: Option<InstanceLock>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            renderer: RendererConfig::default(),
+            source: SourceConfig::default(),
+            visibility: VisibilityConfig::default(),
+            edit: EditConfig::default(),
+            hotkeys: HotkeysConfig::default(),
+            benchmark: BenchmarkConfig::default(),
+            control: ControlConfig::default(),
+            theme: ThemeConfig::default(),
+            widgets: Vec::new(),
+            config_path: None,
+            layout: None,
+            hidden: false,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            instance: None,
+        }
+    }
+
+    /// Window and renderer options plus the built-in widgets listed in `config`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut overlay = Self::new();
+        overlay.apply_config(config);
+        overlay
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.window.title = title.into();
+        self
+    }
+
+    /// Covers every monitor `selectors` pick instead of just the primary one.
+    pub fn with_monitors(mut self, selectors: impl IntoIterator<Item = MonitorSelector>) -> Self {
+        self.window.monitor = selectors.into_iter().collect();
+        self
+    }
+
+    /// Adds a widget; a bare `egui::Pos2` pins it that many points from the
+    /// top-left corner.
+    pub fn with_widget(
+        mut self,
+        placement: impl Into<Placement>,
+        widget: impl Widget + 'static,
+    ) -> Self {
+        self.widgets.push(WidgetSlot {
+            placement: placement.into(),
+            last_update: None,
+            config: None,
+            id: None,
+            kind: "custom",
+            hidden: false,
+            widget: Box::new(widget),
+        });
+        self
+    }
+
+    /// Measures frames of something other than the overlay's own loop.
+    pub fn with_source(mut self, source: SourceConfig) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Shows the widgets only while `rules` hold.
+    pub fn with_visibility(mut self, rules: VisibilityConfig) -> Self {
+        self.visibility = rules;
+        self
+    }
+
+    /// Watches `path` while the overlay runs and applies the config whenever
+    /// the file changes. A file that fails to load leaves the previous config
+    /// in place and shows the error on screen.
+    pub fn watch_config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Starts in `layout` rather than the first layout the widgets name.
+    pub fn with_layout(mut self, layout: impl Into<String>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    /// Starts with the widgets hidden, as if the visibility hotkey had been
+    /// pressed.
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Keeps `lock` for as long as the overlay runs, recording in it where
+    /// its control socket is.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn with_instance_lock(mut self, lock: InstanceLock) -> Self {
+        self.instance = Some(lock);
+        self
+    }
+
+    /// The layouts the widgets name, in the order they first appear.
+    fn layouts(&self) -> Vec<String> {
+        let mut layouts: Vec<String> = Vec::new();
+        for name in self.widgets.iter().flat_map(|slot| &slot.placement.layouts) {
+            if !layouts.contains(name) {
+                layouts.push(name.clone());
+            }
+        }
+        layouts
+    }
+
+    /// How far back the frame tracker has to keep frames for the widgets.
+    fn frame_history(&self) -> Duration {
+        self.widgets
+            .iter()
+            .filter_map(|slot| slot.config.as_ref()?.kind.frame_history())
+            .fold(fps::DEFAULT_HISTORY, Duration::max)
+    }
+
+    /// Whether a widget from the config file shows frame timings.
+    fn reads_frames(&self) -> bool {
+        self.widgets.iter().any(|slot| {
+            slot.config
+                .as_ref()
+                .is_some_and(|config| config.kind.frame_history().is_some())
+        })
+    }
+
+    fn find_widget(&self, widget: &WidgetRef) -> Result<usize, RpcError> {
+        match widget {
+            WidgetRef::Index(index) => (*index < self.widgets.len()).then_some(*index),
+            WidgetRef::Id(id) => self
+                .widgets
+                .iter()
+                .position(|slot| slot.id.as_deref() == Some(id.as_str())),
+        }
+        .ok_or_else(|| RpcError::invalid_params(format!("no widget {widget}")))
+    }
+
+    fn apply_config(&mut self, config: &Config) {
+        self.window = config.window.clone();
+        self.renderer = config.renderer.clone();
+        self.source = config.source.clone();
+        self.visibility = config.visibility.clone();
+        self.edit = config.edit.clone();
+        self.hotkeys = config.hotkeys.clone();
+        self.benchmark = config.benchmark.clone();
+        self.control = config.control.clone();
+        let theme_changed = self.theme != config.theme;
+        self.theme = config.theme.clone();
+
+        let (added, mut previous): (Vec<_>, Vec<_>) = std::mem::take(&mut self.widgets)
+            .into_iter()
+            .partition(|slot| slot.config.is_none());
+        self.widgets = added;
+        for widget in &config.widgets {
+            // An unchanged widget keeps running, with its history, its
+            // commands and whether it was hidden.
+            let unchanged = previous
+                .iter()
+                .position(|slot| !theme_changed && slot.config.as_ref() == Some(widget))
+                .map(|index| previous.remove(index));
+            self.widgets.push(unchanged.unwrap_or_else(|| WidgetSlot {
+                placement: Placement::from_config(widget),
+                last_update: None,
+                config: Some(widget.clone()),
+                id: widget.id.clone(),
+                kind: widget.kind.name(),
+                hidden: false,
+                widget: widgets::from_config(&config.theme, widget),
+            }));
+        }
+    }
+
+    /// Renders the widgets into an image without opening a window. Uses the
+    /// software rasterizer so the result does not depend on the GPU. `size`
+    /// is in physical pixels.
+    pub fn snapshot(
+        &mut self,
+        fps: &FpsTracker,
+        size: [u32; 2],
+        pixels_per_point: f32,
+    ) -> Snapshot {
+        let [width, height] = size;
+        let raw_input = fixed_input(
+            egui::vec2(width as f32, height as f32) / pixels_per_point,
+            pixels_per_point,
+        );
+
+        let ctx = egui::Context::default();
+        let mut toasts = Toasts::default();
+        let mut rasterizer = Rasterizer::default();
+        let now = Instant::now();
+        let shown: Vec<usize> = (0..self.widgets.len()).collect();
+        update_widgets(&mut self.widgets, fps, &Metrics::new(), now);
+        // Anchored areas are measured on their first frame and only drawn
+        // once egui knows their size.
+        let mut frame = build_frame(
+            &ctx,
+            raw_input.clone(),
+            &mut self.widgets,
+            &shown,
+            1.0,
+            Some(&mut toasts),
+            None,
+            now,
+        );
+        for _ in 1..SNAPSHOT_MAX_PASSES {
+            if frame.repaint_delay != Some(Duration::ZERO) {
+                break;
+            }
+            rasterizer.update_textures(&frame.textures_delta);
+            frame = build_frame(
+                &ctx,
+                raw_input.clone(),
+                &mut self.widgets,
+                &shown,
+                1.0,
+                Some(&mut toasts),
+                None,
+                now,
+            );
+        }
+        rasterizer.update_textures(&frame.textures_delta);
+
+        let mut canvas = Canvas::new(width, height);
+        rasterizer.paint(&mut canvas, &frame.paint_jobs, frame.pixels_per_point);
+        Snapshot::from_premultiplied(width, height, &canvas.pixels)
+    }
+
+    /// Opens the overlay windows and runs their event loop until one of them
+    /// closes.
+    pub fn run(self) -> anyhow::Result<()> {
+        #[cfg(windows)]
+        {
+            return crate::windows::run();
+        }
+
+        self.run_winit()
+    }
+
+    fn run_winit(mut self) -> anyhow::Result<()> {
+        let event_loop = EventLoopBuilder::<OverlayEvent>::with_user_event()
+            .build()
+            .context("create event loop")?;
+
+        let mut toasts = Toasts::default();
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let layer_shell = LayerShell::connect(&event_loop, event_loop.create_proxy())
+            .unwrap_or_else(|err| {
+                toasts.push(
+                    ToastKind::Error,
+                    format!("{err:#}; the overlay cannot stay on top or ignore input"),
+                    ERROR_TOAST_DURATION,
+                );
+                None
+            });
+        let watcher = match &self.config_path {
+            Some(path) => match ConfigWatcher::new(path, event_loop.create_proxy()) {
+                Ok(watcher) => Some(watcher),
+                Err(err) => {
+                    toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                    None
+                }
+            },
+            None => None,
+        };
+        let monitor_watcher = MonitorWatcher::start(&event_loop, event_loop.create_proxy())
+            .unwrap_or_else(|err| {
+                toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                None
+            });
+        let compositor_watcher = CompositorWatcher::start(&event_loop, event_loop.create_proxy())
+            .unwrap_or_else(|err| {
+                toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                None
+            });
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let signal_watcher = SignalWatcher::start(event_loop.create_proxy())
+            .map_err(|err| {
+                toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+            })
+            .ok();
+        let hotkeys = start_hotkeys(
+            &self.hotkeys,
+            &event_loop,
+            event_loop.create_proxy(),
+            &mut toasts,
+        );
+        let visibility = start_visibility(&self.visibility, event_loop.create_proxy(), &mut toasts);
+        // With rules, hidden until the watcher first reports.
+        let rules_shown = visibility.is_none();
+        let hidden = self.hidden;
+        let fade = Fade::new(rules_shown && !hidden, self.visibility.fade());
+        let layouts = self.layouts();
+        let layout = match self.layout.take() {
+            Some(layout) if layouts.contains(&layout) => Some(layout),
+            Some(layout) => {
+                toasts.push(
+                    ToastKind::Error,
+                    format!("no widget is in layout `{layout}`"),
+                    ERROR_TOAST_DURATION,
+                );
+                layouts.into_iter().next()
+            }
+            None => layouts.into_iter().next(),
+        };
+        let control = start_control(&self.control, event_loop.create_proxy(), &mut toasts);
+        let source = start_source(&self.source, self.reads_frames(), &mut toasts);
+        let schedule = RedrawSchedule::new(self.renderer.min_frame_interval());
+        let fps_tracker = FpsTracker::new().with_history(self.frame_history());
+
+        let mut runtime = Runtime {
+            overlay: self,
+            windows: Vec::new(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            layer_shell,
+            fps_tracker,
+            source,
+            toasts,
+            schedule,
+            watcher,
+            saved_config: None,
+            _monitor_watcher: monitor_watcher,
+            _compositor_watcher: compositor_watcher,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            _signal_watcher: signal_watcher,
+            // Until the watcher says otherwise.
+            composited: true,
+            follower: None,
+            visibility,
+            rules_shown,
+            hidden,
+            fade,
+            edit: None,
+            hotkeys,
+            layout,
+            benchmark: None,
+            metrics: Metrics::new(),
+            control,
+            proxy: event_loop.create_proxy(),
+        };
+        // The socket listens by now, so overlays started later can connect.
+        runtime.publish_control();
+        let monitors = runtime.monitors(&event_loop);
+        for monitor in window_targets(&runtime.overlay.window, &monitors) {
+            let window = runtime.open_window(&event_loop, monitor)?;
+            runtime.windows.push(window);
+        }
+        assign_widgets(
+            &mut runtime.windows,
+            &runtime.overlay.widgets,
+            &monitors,
+            runtime.layout.as_deref(),
+        );
+        runtime.start_follower();
+
+        event_loop
+            .run(move |event, target| runtime.handle_event(event, target))
+            .context("run event loop")
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One transparent window covering a monitor, with its own surface and egui
+/// context.
+struct OverlayWindow {
+    surface: Surface,
+    /// `None` when the windowing system reports no monitors at all.
+    monitor: Option<Monitor>,
+    renderer: Renderer,
+    ctx: egui::Context,
+    /// Indices into `Overlay::widgets` of the widgets drawn here.
+    widgets: Vec<usize>,
+    /// Set for X11 windows.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    shaper: Option<WindowShaper>,
+    /// Set while an X11 window is meant to stay on top.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    stack_keeper: Option<StackKeeper>,
+}
+
+/// What an overlay window is drawn on.
+enum Surface {
+    Window {
+        window: Arc<Window>,
+        state: Box<egui_winit::State>,
+    },
+    /// A layer surface, which takes no input and needs no platform tweaks.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    Layer(LayerWindow),
+}
+
+impl Surface {
+    fn window(&self) -> Option<&Arc<Window>> {
+        match self {
+            Self::Window { window, .. } => Some(window),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(_) => None,
+        }
+    }
+
+    fn render_target(&self) -> RenderTarget {
+        match self {
+            Self::Window { window, .. } => RenderTarget::Window(window.clone()),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(layer) => RenderTarget::Layer(layer.handle()),
+        }
+    }
+
+    /// Layer surfaces close for good when their output goes away.
+    fn is_closed(&self) -> bool {
+        match self {
+            Self::Window { .. } => false,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(layer) => layer.is_closed(),
+        }
+    }
+
+    /// Windows redraw on `WindowEvent::RedrawRequested`, layer surfaces
+    /// right before the event loop waits.
+    fn request_redraw(&mut self) {
+        match self {
+            Self::Window { window, .. } => window.request_redraw(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(layer) => layer.request_redraw(),
+        }
+    }
+
+    fn take_egui_input(&mut self) -> egui::RawInput {
+        match self {
+            Self::Window { window, state } => state.take_egui_input(window),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(layer) => fixed_input(layer.size_in_points(), layer.pixels_per_point()),
+        }
+    }
+
+    fn handle_platform_output(&mut self, output: egui::PlatformOutput) {
+        match self {
+            Self::Window { window, state } => state.handle_platform_output(window, output),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(_) => {}
+        }
+    }
+
+    /// Hands input to `ctx` from now on.
+    fn set_egui_ctx(&mut self, ctx: &egui::Context) {
+        match self {
+            Self::Window { window, state } => {
+                **state = egui_winit::State::new(
+                    ctx.clone(),
+                    egui::ViewportId::ROOT,
+                    window.as_ref(),
+                    Some(window.scale_factor() as f32),
+                    None,
+                );
+            }
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(_) => {}
+        }
+    }
+}
+
+impl OverlayWindow {
+    /// Starts over with a fresh egui context, whose first frame sends every
+    /// texture again. Clearing the old one's memory would not resend the
+    /// font atlas, which a new renderer has never seen.
+    fn reset_ctx(&mut self) {
+        let ctx = egui::Context::default();
+        ctx.set_style(self.ctx.style());
+        // Scales the native pixels per point the same way as before.
+        ctx.set_zoom_factor(self.ctx.zoom_factor());
+        self.surface.set_egui_ctx(&ctx);
+        self.ctx = ctx;
+    }
+}
+
+async fn open_window(
+    target: &EventLoopWindowTarget<OverlayEvent>,
+    config: &WindowConfig,
+    renderer: &RendererConfig,
+    monitor: Option<Monitor>,
+    toasts: &mut Toasts,
+) -> anyhow::Result<OverlayWindow> {
+    let mut builder = WindowBuilder::new();
+    builder = builder
+        .with_title(&config.title)
+        .with_decorations(false)
+        .with_resizable(false)
+        .with_transparent(true)
+        .with_window_level(config.effective_level().into())
+        // A following window shows up once its target does.
+        .with_visible(config.follow.is_none());
+    #[cfg(windows)]
+    {
+        builder = builder
+            .with_no_redirection_bitmap(true)
+            .with_skip_taskbar(true);
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        // Notifications float above other windows without taking focus in
+        // most window managers; utility windows are the fallback.
+        builder = builder
+            .with_x11_window_type(vec![XWindowType::Notification, XWindowType::Utility])
+            .with_override_redirect(config.override_redirect);
+    }
+    let window = Arc::new(builder.build(target).context("create window")?);
+
+    platform::configure_overlay(&window);
+    if let Some(monitor) = &monitor {
+        place_on_monitor(&window, monitor);
+    }
+
+    let renderer = Renderer::new(RenderTarget::Window(window.clone()), renderer, |reason| {
+        toasts.push(ToastKind::Info, reason, STATUS_TOAST_DURATION);
+    })
+    .await?;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let shaper = crate::x11::window_xid(&window).and_then(|xid| {
+        WindowShaper::new(xid)
+            .map_err(|err| {
+                toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+            })
+            .ok()
+    });
+
+    let ctx = egui::Context::default();
+    let state = egui_winit::State::new(ctx.clone(), egui::ViewportId::ROOT, target, None, None);
+
+    Ok(OverlayWindow {
+        surface: Surface::Window {
+            window,
+            state: Box::new(state),
+        },
+        monitor,
+        renderer,
+        ctx,
+        widgets: Vec::new(),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        shaper,
+        #[cfg(all(unix, not(target_os = "macos")))]
+        stack_keeper: None,
+    })
+}
+
+/// Opens a layer surface on `monitor`, which stays on top and lets input
+/// through without any help from the platform.
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn open_layer(
+    layer_shell: &mut LayerShell,
+    renderer: &RendererConfig,
+    monitor: Option<Monitor>,
+    toasts: &mut Toasts,
+) -> anyhow::Result<OverlayWindow> {
+    let output = match &monitor {
+        Some(Monitor::Output(output)) => Some(output),
+        _ => None,
+    };
+    let layer = layer_shell.open(output)?;
+    let renderer = Renderer::new(RenderTarget::Layer(layer.handle()), renderer, |reason| {
+        toasts.push(ToastKind::Info, reason, STATUS_TOAST_DURATION);
+    })
+    .await?;
+
+    Ok(OverlayWindow {
+        surface: Surface::Layer(layer),
+        monitor,
+        renderer,
+        ctx: egui::Context::default(),
+        widgets: Vec::new(),
+        shaper: None,
+        stack_keeper: None,
+    })
+}
+
+/// Everything the running event loop owns.
+struct Runtime {
+    overlay: Overlay,
+    /// Never empty; toasts are drawn on the first window.
+    windows: Vec<OverlayWindow>,
+    /// Set on compositors with wlr-layer-shell, whose surfaces stand in for
+    /// winit windows.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    layer_shell: Option<LayerShell>,
+    fps_tracker: FpsTracker,
+    source: Option<FrameSource>,
+    toasts: Toasts,
+    schedule: RedrawSchedule,
+    watcher: Option<ConfigWatcher>,
+    /// What edit mode last wrote to the config file.
+    saved_config: Option<String>,
+    _monitor_watcher: Option<MonitorWatcher>,
+    _compositor_watcher: Option<CompositorWatcher>,
+    #[cfg(all(unix, not(target_os = "macos")))]
+    _signal_watcher: Option<SignalWatcher>,
+    /// Cleared on X11 without a compositing manager, where the windows are
+    /// shaped to their widgets instead.
+    composited: bool,
+    /// Set while `[window] follow` is.
+    follower: Option<WindowFollower>,
+    /// Set while there are `[visibility]` rules.
+    visibility: Option<VisibilityWatcher>,
+    /// Whether the `[visibility]` rules show the widgets.
+    rules_shown: bool,
+    /// Set by the visibility hotkey or the control socket, which hide the
+    /// widgets whatever the rules say.
+    hidden: bool,
+    fade: Fade,
+    /// Set while in edit mode.
+    edit: Option<EditSession>,
+    /// Set while there are `[hotkeys]` bindings.
+    hotkeys: Option<HotkeyWatcher>,
+    /// `None` when the widgets name no layouts.
+    layout: Option<String>,
+    /// Set while capturing frame times.
+    benchmark: Option<Benchmark>,
+    /// Samples pushed over the control socket.
+    metrics: Metrics,
+    /// Set while `[control]` is enabled.
+    control: Option<ControlServer>,
+    proxy: EventLoopProxy<OverlayEvent>,
+}
+
+impl Runtime {
+    fn handle_event(
+        &mut self,
+        event: Event<OverlayEvent>,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+    ) {
+        match event {
+            Event::WindowEvent {
+                mut event,
+                window_id,
+            } => {
+                let Some(index) = self.windows.iter().position(|window| {
+                    window
+                        .surface
+                        .window()
+                        .is_some_and(|window| window.id() == window_id)
+                }) else {
+                    return;
+                };
+                match event {
+                    WindowEvent::CloseRequested => {
+                        target.exit();
+                        return;
+                    }
+                    WindowEvent::Resized(size) => {
+                        self.windows[index].renderer.resize(size.width, size.height);
+                    }
+                    WindowEvent::ScaleFactorChanged {
+                        ref mut inner_size_writer,
+                        ..
+                    } => {
+                        // winit would scale the window; keep its physical
+                        // size, which matches its monitor or target, and let
+                        // egui lay out at the new scale.
+                        if let Some(window) = self.windows[index].surface.window() {
+                            let _ = inner_size_writer.request_inner_size(window.inner_size());
+                            window.request_redraw();
+                        }
+                    }
+                    WindowEvent::RedrawRequested => {
+                        if !self.redraw(index) {
+                            target.exit();
+                        }
+                        return;
+                    }
+                    _ => {}
+                }
+
+                if let Surface::Window { window, state } = &mut self.windows[index].surface
+                    && state.on_window_event(window, &event).repaint
+                {
+                    window.request_redraw();
+                }
+            }
+            Event::UserEvent(OverlayEvent::ConfigChanged) => self.reload_config(target),
+            Event::UserEvent(OverlayEvent::Target(geometry)) => self.follow_target(geometry),
+            Event::UserEvent(OverlayEvent::Visibility(shown)) => {
+                self.rules_shown = shown;
+                self.update_fade();
+            }
+            Event::UserEvent(OverlayEvent::Status(message)) => {
+                self.toasts
+                    .push(ToastKind::Info, message, STATUS_TOAST_DURATION);
+                self.request_redraw();
+            }
+            Event::UserEvent(OverlayEvent::MonitorsChanged) => self.monitors_changed(target),
+            Event::UserEvent(OverlayEvent::Compositing(composited)) => {
+                self.set_composited(composited);
+            }
+            // Dispatched below, like everything else the connection received.
+            Event::UserEvent(OverlayEvent::Wayland) => {}
+            Event::UserEvent(OverlayEvent::ToggleEditMode) => self.toggle_edit_mode(),
+            Event::UserEvent(OverlayEvent::Hotkey(action)) => match action {
+                HotkeyAction::ToggleVisibility => {
+                    self.hidden = !self.hidden;
+                    self.update_fade();
+                }
+                HotkeyAction::ToggleEditMode => self.toggle_edit_mode(),
+                HotkeyAction::NextLayout => self.next_layout(target),
+                HotkeyAction::ToggleBenchmark => self.toggle_benchmark(),
+            },
+            Event::UserEvent(OverlayEvent::Control(ControlCall { request, reply })) => {
+                let _ = reply.send(self.control(target, request));
+            }
+            Event::NewEvents(StartCause::Init | StartCause::ResumeTimeReached { .. }) => {
+                self.advance();
+            }
+            Event::AboutToWait => {
+                #[cfg(all(unix, not(target_os = "macos")))]
+                if !self.draw_layers(target) {
+                    target.exit();
+                    return;
+                }
+                let next = self.schedule.next(Instant::now());
+                target.set_control_flow(ControlFlow::WaitUntil(next));
+            }
+            _ => {}
+        }
+    }
+
+    /// Takes in the frames the source timed and updates the due widgets once
+    /// for all windows, then asks each window to redraw.
+    fn advance(&mut self) {
+        // The overlay's own redraws follow the schedule, not the game, so
+        // without a source there is nothing to count.
+        if let Some(source) = &self.source {
+            source.drain(&mut self.fps_tracker, |message| {
+                self.toasts
+                    .push(ToastKind::Info, message, STATUS_TOAST_DURATION);
+            });
+        }
+        if let Some(benchmark) = &mut self.benchmark {
+            benchmark.record(&self.fps_tracker);
+        }
+
+        let now = Instant::now();
+        update_widgets(
+            &mut self.overlay.widgets,
+            &self.fps_tracker,
+            &self.metrics,
+            now,
+        );
+        self.schedule_next(now);
+        self.request_redraw();
+    }
+
+    /// Returns `false` when the window can no longer be rendered to.
+    fn redraw(&mut self, index: usize) -> bool {
+        let window = &mut self.windows[index];
+        let now = Instant::now();
+        let raw_input = window.surface.take_egui_input();
+        // Hidden widgets could not be found to be moved.
+        let opacity = match self.edit {
+            Some(_) => 1.0,
+            None => self.fade.opacity(now),
+        };
+        let frame = build_frame(
+            &window.ctx,
+            raw_input,
+            &mut self.overlay.widgets,
+            &window.widgets,
+            opacity,
+            (index == 0).then_some(&mut self.toasts),
+            self.edit.as_mut(),
+            now,
+        );
+        if self.fade.is_fading(now) {
+            self.schedule.request(now + FADE_FRAME_INTERVAL);
+        }
+
+        window.surface.handle_platform_output(frame.platform_output);
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Some(shaper) = &mut window.shaper {
+            shaper.update_textures(&frame.textures_delta);
+            if let Some(edit) = &self.edit
+                && let Err(err) = shaper.set_input(edit.input(), frame.pixels_per_point)
+            {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+            }
+            if !self.composited
+                && let Some(size) = window.surface.window().map(|window| window.inner_size())
+                && let Err(err) = shaper.shape(
+                    &frame.paint_jobs,
+                    frame.pixels_per_point,
+                    size.width,
+                    size.height,
+                )
+            {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                window.shaper = None;
+            }
+        }
+        if let Some(at) = frame.repaint_delay.and_then(|delay| now.checked_add(delay)) {
+            self.schedule.request(at);
+        }
+
+        let painted = window.renderer.paint(
+            &frame.paint_jobs,
+            &frame.textures_delta,
+            frame.pixels_per_point,
+        );
+        if let Some(edit) = &mut self.edit {
+            let moved = edit.take_moved();
+            let done = edit.is_done();
+            self.save_placements(&moved);
+            if done {
+                self.toggle_edit_mode();
+            }
+        }
+        painted
+    }
+
+    /// Queues the next frame for whatever changes first after the one started
+    /// at `now`: a widget falling due or a toast expiring. Windows add their
+    /// own egui animations as they redraw.
+    fn schedule_next(&mut self, now: Instant) {
+        self.schedule.redrawn(now);
+        for slot in &self.overlay.widgets {
+            self.schedule.request(slot.next_update().unwrap_or(now));
+        }
+        if let Some(expiry) = self.toasts.next_expiry() {
+            self.schedule.request(expiry);
+        }
+    }
+
+    fn request_redraw(&mut self) {
+        for window in &mut self.windows {
+            window.surface.request_redraw();
+        }
+    }
+
+    /// Blends the windows through the compositing manager, or without one
+    /// shapes them so only the widgets' pixels show.
+    fn set_composited(&mut self, composited: bool) {
+        if self.composited == composited {
+            return;
+        }
+        self.composited = composited;
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if composited {
+            for window in &mut self.windows {
+                if let Some(shaper) = &mut window.shaper
+                    && let Err(err) = shaper.unshape()
+                {
+                    self.toasts
+                        .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                    window.shaper = None;
+                }
+            }
+        }
+        let message = match composited {
+            true => "compositing manager running; blending the overlay",
+            false => "no compositing manager; showing only the widgets",
+        };
+        self.toasts
+            .push(ToastKind::Info, message, STATUS_TOAST_DURATION);
+        self.request_redraw();
+    }
+
+    fn update_fade(&mut self) {
+        let shown = self.rules_shown && !self.hidden;
+        let fade = self.overlay.visibility.fade();
+        self.fade.set(shown, fade, Instant::now());
+        self.request_redraw();
+    }
+
+    /// Switches to the layout after the current one, wrapping around.
+    fn next_layout(&mut self, target: &EventLoopWindowTarget<OverlayEvent>) {
+        let layouts = self.overlay.layouts();
+        if layouts.is_empty() {
+            self.toasts.push(
+                ToastKind::Error,
+                "no widget names a layout to switch to",
+                ERROR_TOAST_DURATION,
+            );
+            return;
+        }
+        let next = self
+            .layout
+            .as_ref()
+            .and_then(|current| layouts.iter().position(|name| name == current))
+            .map_or(0, |index| (index + 1) % layouts.len());
+        self.toasts.push(
+            ToastKind::Info,
+            format!("layout {}", layouts[next]),
+            STATUS_TOAST_DURATION,
+        );
+        self.layout = Some(layouts[next].clone());
+        self.reassign_widgets(target);
+    }
+
+    /// Puts the widgets on the windows again after the layout or a widget's
+    /// `hidden` changed.
+    fn reassign_widgets(&mut self, target: &EventLoopWindowTarget<OverlayEvent>) {
+        let monitors = self.monitors(target);
+        assign_widgets(
+            &mut self.windows,
+            &self.overlay.widgets,
+            &monitors,
+            self.layout.as_deref(),
+        );
+        self.request_redraw();
+    }
+
+    /// Carries out a request from a control socket client.
+    fn control(
+        &mut self,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        request: ControlRequest,
+    ) -> Result<Value, RpcError> {
+        match request {
+            ControlRequest::ListWidgets {} => {
+                let layout = self.layout.as_deref();
+                let widgets: Vec<Value> = self
+                    .overlay
+                    .widgets
+                    .iter()
+                    .enumerate()
+                    .map(|(index, slot)| {
+                        json!({
+                            "index": index,
+                            "id": slot.id,
+                            "type": slot.kind,
+                            "layouts": slot.placement.layouts,
+                            "shown": !slot.hidden && slot.placement.in_layout(layout),
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(widgets))
+            }
+            ControlRequest::SetText { widget, text } => {
+                let index = self.overlay.find_widget(&widget)?;
+                if !self.overlay.widgets[index].widget.set_text(&text) {
+                    return Err(RpcError::invalid_params(format!(
+                        "widget {widget} shows no text of its own"
+                    )));
+                }
+                self.request_redraw();
+                Ok(Value::Null)
+            }
+            ControlRequest::Push { metric, values } => {
+                let now = Instant::now();
+                for value in values {
+                    self.metrics.push(&metric, value, now);
+                }
+                Ok(Value::Null)
+            }
+            ControlRequest::Show { widget } => self.show(target, widget.as_ref(), true),
+            ControlRequest::Hide { widget } => self.show(target, widget.as_ref(), false),
+            ControlRequest::Layout { name } => {
+                let layouts = self.overlay.layouts();
+                if let Some(name) = name {
+                    if !layouts.contains(&name) {
+                        return Err(RpcError::invalid_params(format!(
+                            "no widget is in layout `{name}`"
+                        )));
+                    }
+                    self.layout = Some(name);
+                    self.reassign_widgets(target);
+                }
+                Ok(json!({ "layout": self.layout, "layouts": layouts }))
+            }
+            ControlRequest::Toast {
+                message,
+                error,
+                duration_ms,
+            } => {
+                let (kind, duration) = match error {
+                    true => (ToastKind::Error, ERROR_TOAST_DURATION),
+                    false => (ToastKind::Info, STATUS_TOAST_DURATION),
+                };
+                let duration = duration_ms.map_or(duration, Duration::from_millis);
+                self.toasts.push(kind, message, duration);
+                self.request_redraw();
+                Ok(Value::Null)
+            }
+            ControlRequest::Stats { window_ms } => {
+                let window = window_ms.map_or(STATS_WINDOW, Duration::from_millis);
+                Ok(json!({
+                    "fps": self.fps_tracker.fps(),
+                    "stats": self.fps_tracker.stats(window),
+                }))
+            }
+            ControlRequest::LoadConfig { path } => {
+                if !path.is_absolute() {
+                    return Err(RpcError::invalid_params(format!(
+                        "{} is not an absolute path",
+                        path.display()
+                    )));
+                }
+                self.load_config(target, path)
+            }
+        }
+    }
+
+    /// Shows or hides `widget`, or the whole overlay without one.
+    fn show(
+        &mut self,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        widget: Option<&WidgetRef>,
+        shown: bool,
+    ) -> Result<Value, RpcError> {
+        match widget {
+            Some(widget) => {
+                let index = self.overlay.find_widget(widget)?;
+                self.overlay.widgets[index].hidden = !shown;
+                self.reassign_widgets(target);
+            }
+            None => {
+                self.hidden = !shown;
+                self.update_fade();
+            }
+        }
+        Ok(Value::Null)
+    }
+
+    /// Starts capturing frame times, or stops and saves the capture with a
+    /// summary on screen.
+    fn toggle_benchmark(&mut self) {
+        let Some(benchmark) = self.benchmark.take() else {
+            self.benchmark = Some(Benchmark::start());
+            self.toasts
+                .push(ToastKind::Info, "benchmark started", STATUS_TOAST_DURATION);
+            self.request_redraw();
+            return;
+        };
+        let elapsed = benchmark.elapsed();
+        let saved = self
+            .overlay
+            .benchmark
+            .output_dir()
+            .context("no directory to save benchmarks in; set [benchmark] output_dir")
+            .and_then(|dir| benchmark.save(&dir));
+        match saved {
+            Ok((path, stats)) => self.toasts.push(
+                ToastKind::Info,
+                format!(
+                    "{} frames in {:.1} s: {:.1} fps average, {:.1} fps 1% low; saved to {}",
+                    stats.frames,
+                    elapsed.as_secs_f32(),
+                    stats.mean_fps,
+                    stats.low_1_fps,
+                    path.display()
+                ),
+                ERROR_TOAST_DURATION,
+            ),
+            Err(err) => {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION)
+            }
+        }
+        self.request_redraw();
+    }
+
+    /// In edit mode the windows take input where the widgets are; outside
+    /// it clicks go through them everywhere.
+    fn toggle_edit_mode(&mut self) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            if self.edit.take().is_some() {
+                for window in &mut self.windows {
+                    if let Some(shaper) = &mut window.shaper
+                        && let Err(err) = shaper.set_input(&[], 1.0)
+                    {
+                        self.toasts.push(
+                            ToastKind::Error,
+                            format!("{err:#}"),
+                            ERROR_TOAST_DURATION,
+                        );
+                    }
+                }
+                self.toasts
+                    .push(ToastKind::Info, "left edit mode", STATUS_TOAST_DURATION);
+            } else if self.windows.iter().any(|window| window.shaper.is_some()) {
+                self.edit = Some(EditSession::new(self.overlay.edit.grid));
+            } else {
+                self.toasts.push(
+                    ToastKind::Error,
+                    "edit mode needs an X11 window",
+                    ERROR_TOAST_DURATION,
+                );
+            }
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        self.toasts.push(
+            ToastKind::Error,
+            "edit mode is only supported on X11",
+            ERROR_TOAST_DURATION,
+        );
+        self.request_redraw();
+    }
+
+    /// Writes where the widgets at `moved` are now into the config file.
+    /// Widgets added in code have no table there and only move until the
+    /// overlay exits.
+    fn save_placements(&mut self, moved: &[usize]) {
+        let Some(path) = &self.overlay.config_path else {
+            return;
+        };
+        let placements: Vec<(usize, &Placement)> = moved
+            .iter()
+            .filter(|&&index| self.overlay.widgets[index].config.is_some())
+            .map(|&index| {
+                let table = self.overlay.widgets[..index]
+                    .iter()
+                    .filter(|slot| slot.config.is_some())
+                    .count();
+                (table, &self.overlay.widgets[index].placement)
+            })
+            .collect();
+        if placements.is_empty() {
+            return;
+        }
+        match edit::save_placements(path, &placements) {
+            Ok(text) => self.saved_config = Some(text),
+            Err(err) => {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                return;
+            }
+        }
+        // What the file says now, so a later reload keeps these widgets.
+        for &index in moved {
+            let slot = &mut self.overlay.widgets[index];
+            if let Some(config) = &mut slot.config {
+                config.offset = slot.placement.offset.map(edit::saved_number);
+                config.scale = edit::saved_number(slot.placement.scale);
+            }
+        }
+    }
+
+    fn monitors(&self, target: &EventLoopWindowTarget<OverlayEvent>) -> Monitors {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Some(layer_shell) = &self.layer_shell {
+            return layer_shell.monitors();
+        }
+        Monitors::query(target)
+    }
+
+    fn monitors_changed(&mut self, target: &EventLoopWindowTarget<OverlayEvent>) {
+        let monitors = self.monitors(target);
+        self.sync_windows(target, &monitors);
+        assign_widgets(
+            &mut self.windows,
+            &self.overlay.widgets,
+            &monitors,
+            self.layout.as_deref(),
+        );
+        self.request_redraw();
+    }
+
+    fn reload_config(&mut self, target: &EventLoopWindowTarget<OverlayEvent>) {
+        let Some(path) = self.overlay.config_path.clone() else {
+            return;
+        };
+        // Saving placements in edit mode changes the file too, but leaves
+        // nothing to apply.
+        if let Some(saved) = &self.saved_config
+            && fs::read_to_string(&path).is_ok_and(|text| text == *saved)
+        {
+            return;
+        }
+        self.saved_config = None;
+        match Config::load(&path) {
+            Ok(config) => self.apply_config(target, &config),
+            Err(err) => {
+                self.toasts
+                    .push(ToastKind::Error, err.to_string(), ERROR_TOAST_DURATION);
+            }
+        }
+    }
+
+    /// Switches to the config file at `path`, as asked by a second instance
+    /// or over the control socket.
+    fn load_config(
+        &mut self,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        path: PathBuf,
+    ) -> Result<Value, RpcError> {
+        let config =
+            Config::load(&path).map_err(|err| RpcError::invalid_params(err.to_string()))?;
+        self.watcher = None;
+        self.watcher = ConfigWatcher::new(&path, self.proxy.clone())
+            .map_err(|err| {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+            })
+            .ok();
+        self.overlay.config_path = Some(path);
+        self.apply_config(target, &config);
+        Ok(Value::Null)
+    }
+
+    /// Tells the overlays started later where the control socket now is.
+    fn publish_control(&mut self) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Some(lock) = &self.overlay.instance {
+            let socket = self
+                .control
+                .as_ref()
+                .and_then(|_| self.overlay.control.socket_path());
+            if let Err(err) = lock.publish(socket.as_deref()) {
+                self.toasts.push(
+                    ToastKind::Error,
+                    format!("record the control socket: {err}"),
+                    ERROR_TOAST_DURATION,
+                );
+            }
+        }
+    }
+
+    /// Applies a changed config, restarting only what it changed.
+    fn apply_config(&mut self, target: &EventLoopWindowTarget<OverlayEvent>, config: &Config) {
+        let previous = self.overlay.window.clone();
+        let previous_backend = self.overlay.renderer.backend;
+        let previous_source = self.overlay.source.clone();
+        let previous_visibility = self.overlay.visibility.clone();
+        let previous_hotkeys = self.overlay.hotkeys.clone();
+        let previous_control = self.overlay.control.clone();
+        self.overlay.apply_config(config);
+
+        if self.overlay.visibility != previous_visibility {
+            self.visibility = None;
+            self.visibility = start_visibility(
+                &self.overlay.visibility,
+                self.proxy.clone(),
+                &mut self.toasts,
+            );
+            if self.visibility.is_none() {
+                self.rules_shown = true;
+                self.update_fade();
+            }
+        }
+
+        if self.overlay.hotkeys != previous_hotkeys {
+            // The old grabs have to go before the new ones are made.
+            self.hotkeys = None;
+            self.hotkeys = start_hotkeys(
+                &self.overlay.hotkeys,
+                target,
+                self.proxy.clone(),
+                &mut self.toasts,
+            );
+        }
+
+        if self.overlay.control != previous_control {
+            // The old server removes its socket as it stops.
+            self.control = None;
+            self.control =
+                start_control(&self.overlay.control, self.proxy.clone(), &mut self.toasts);
+            self.publish_control();
+        }
+
+        let layouts = self.overlay.layouts();
+        if !self
+            .layout
+            .as_ref()
+            .is_some_and(|layout| layouts.contains(layout))
+        {
+            self.layout = layouts.into_iter().next();
+        }
+
+        if self.overlay.source != previous_source {
+            self.source = None;
+            self.source = start_source(
+                &self.overlay.source,
+                self.overlay.reads_frames(),
+                &mut self.toasts,
+            );
+            self.fps_tracker = FpsTracker::new();
+        }
+        let history = self.overlay.frame_history();
+        self.fps_tracker = std::mem::take(&mut self.fps_tracker).with_history(history);
+
+        let window_config = &self.overlay.window;
+        for window in self
+            .windows
+            .iter()
+            .filter_map(|window| window.surface.window())
+        {
+            if window_config.title != previous.title {
+                window.set_title(&window_config.title);
+            }
+            if window_config.effective_level() != previous.effective_level() {
+                window.set_window_level(window_config.effective_level().into());
+            }
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if window_config.effective_level() != previous.effective_level() {
+            for window in &mut self.windows {
+                window.stack_keeper = None;
+                window.stack_keeper =
+                    keep_on_top(window, &self.overlay.window, &self.proxy, &mut self.toasts);
+            }
+        }
+        if self.overlay.renderer.backend != previous_backend {
+            for index in 0..self.windows.len() {
+                self.switch_renderer(index);
+            }
+        }
+        let monitors = self.monitors(target);
+        let window_config = &self.overlay.window;
+        if window_config.override_redirect != previous.override_redirect {
+            self.reopen_windows(target, &monitors);
+        } else if window_config.monitor != previous.monitor
+            || window_config.follow != previous.follow
+        {
+            self.sync_windows(target, &monitors);
+        }
+        if self.overlay.window.follow != previous.follow
+            || self.overlay.window.override_redirect != previous.override_redirect
+        {
+            self.start_follower();
+        }
+        for window in &mut self.windows {
+            window
+                .renderer
+                .set_present_mode(self.overlay.renderer.present_mode.into());
+        }
+        assign_widgets(
+            &mut self.windows,
+            &self.overlay.widgets,
+            &monitors,
+            self.layout.as_deref(),
+        );
+        self.schedule
+            .set_min_interval(self.overlay.renderer.min_frame_interval());
+        self.request_redraw();
+    }
+}
+
+impl Runtime {
+    /// (Re)starts following the window `[window] follow` selects with the
+    /// first overlay window, or stops following when it is unset.
+    fn start_follower(&mut self) {
+        self.follower = None;
+        let Some(selector) = &self.overlay.window.follow else {
+            return;
+        };
+        let Some(window) = self.windows[0].surface.window() else {
+            self.toasts.push(
+                ToastKind::Error,
+                "following a window needs an X11 session",
+                ERROR_TOAST_DURATION,
+            );
+            return;
+        };
+        match WindowFollower::start(window, selector, self.proxy.clone()) {
+            Ok(follower) => self.follower = Some(follower),
+            Err(err) => {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+                // Without a target the window would never show up.
+                window.set_visible(true);
+            }
+        }
+    }
+
+    fn follow_target(&mut self, geometry: TargetGeometry) {
+        let Some(window) = self.windows[0]
+            .surface
+            .window()
+            .filter(|_| self.follower.is_some())
+        else {
+            return;
+        };
+        match geometry {
+            TargetGeometry::Hidden => window.set_visible(false),
+            TargetGeometry::Visible { position, size } => {
+                window.set_outer_position(position);
+                let _ = window.request_inner_size(size);
+                window.set_visible(true);
+                window.request_redraw();
+            }
+        }
+    }
+
+    /// Recreates the renderer of window `index` for a changed `backend`,
+    /// keeping the current one if the new one cannot be created.
+    fn switch_renderer(&mut self, index: usize) {
+        let toasts = &mut self.toasts;
+        let window = &mut self.windows[index];
+        let renderer = pollster::block_on(Renderer::new(
+            window.surface.render_target(),
+            &self.overlay.renderer,
+            |reason| toasts.push(ToastKind::Info, reason, STATUS_TOAST_DURATION),
+        ));
+        match renderer {
+            Ok(renderer) => {
+                window.renderer = renderer;
+                // The new backend has none of the old one's textures.
+                window.reset_ctx();
+            }
+            Err(err) => {
+                self.toasts
+                    .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION)
+            }
+        }
+    }
+
+    /// Opens a window on `monitor`: a layer surface when the compositor has
+    /// them, a winit window otherwise.
+    fn open_window(
+        &mut self,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        monitor: Option<Monitor>,
+    ) -> anyhow::Result<OverlayWindow> {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Some(layer_shell) = &mut self.layer_shell {
+            return pollster::block_on(open_layer(
+                layer_shell,
+                &self.overlay.renderer,
+                monitor,
+                &mut self.toasts,
+            ));
+        }
+        let window = pollster::block_on(open_window(
+            target,
+            &self.overlay.window,
+            &self.overlay.renderer,
+            monitor,
+            &mut self.toasts,
+        ));
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let window = window.map(|mut window| {
+            window.stack_keeper =
+                keep_on_top(&window, &self.overlay.window, &self.proxy, &mut self.toasts);
+            window
+        });
+        window
+    }
+
+    /// Applies what the compositor sent about the layer surfaces and outputs,
+    /// then draws the surfaces that asked for it. Returns `false` when the
+    /// connection or a surface can no longer be used.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn draw_layers(&mut self, target: &EventLoopWindowTarget<OverlayEvent>) -> bool {
+        let Some(layer_shell) = &mut self.layer_shell else {
+            return true;
+        };
+        let Ok(events) = layer_shell.dispatch() else {
+            return false;
+        };
+        for event in &events {
+            for window in &mut self.windows {
+                if let Surface::Layer(layer) = &mut window.surface
+                    && layer.apply(event)
+                {
+                    let size = layer.physical_size();
+                    window.renderer.resize(size.width, size.height);
+                }
+            }
+        }
+        if events
+            .iter()
+            .any(|event| matches!(event, LayerEvent::OutputsChanged | LayerEvent::Closed(_)))
+        {
+            self.monitors_changed(target);
+        }
+
+        for index in 0..self.windows.len() {
+            if let Surface::Layer(layer) = &mut self.windows[index].surface
+                && layer.take_redraw()
+                && !self.redraw(index)
+            {
+                return false;
+            }
+        }
+        if let Some(layer_shell) = &self.layer_shell {
+            layer_shell.flush();
+        }
+        true
+    }
+
+    /// Leaves one window on each monitor the config selects: windows already
+    /// on a selected monitor stay and follow its current geometry, the
+    /// others close and missing ones open. The old windows stay if none of
+    /// the new ones can be opened.
+    fn sync_windows(&mut self, target: &EventLoopWindowTarget<OverlayEvent>, monitors: &Monitors) {
+        let mut previous = std::mem::take(&mut self.windows);
+        for monitor in window_targets(&self.overlay.window, monitors) {
+            if let Some(kept) = previous
+                .iter()
+                .position(|window| window.monitor == monitor && !window.surface.is_closed())
+            {
+                let mut window = previous.remove(kept);
+                if let (Some(window), Some(monitor)) = (window.surface.window(), &monitor) {
+                    place_on_monitor(window, monitor);
+                }
+                // Monitor handles compare by identity; keep the fresh one
+                // with the current mode.
+                window.monitor = monitor;
+                self.windows.push(window);
+                continue;
+            }
+            match self.open_window(target, monitor) {
+                Ok(window) => self.windows.push(window),
+                Err(err) => {
+                    self.toasts
+                        .push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION)
+                }
+            }
+        }
+        if self.windows.is_empty() {
+            self.windows = previous;
+        }
+    }
+
+    /// Replaces every window with a freshly opened one, for settings that
+    /// only apply when a window is created. The old windows stay if none of
+    /// the new ones can be opened.
+    fn reopen_windows(
+        &mut self,
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        monitors: &Monitors,
+    ) {
+        let previous = std::mem::take(&mut self.windows);
+        self.sync_windows(target, monitors);
+        if self.windows.is_empty() {
+            self.windows = previous;
+        }
+    }
+}
+
+/// Starts raising `window` over fullscreen windows when it is an X11
+/// window meant to stay on top.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keep_on_top(
+    window: &OverlayWindow,
+    config: &WindowConfig,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    toasts: &mut Toasts,
+) -> Option<StackKeeper> {
+    if config.effective_level() != WindowLevel::AlwaysOnTop {
+        return None;
+    }
+    let xid = crate::x11::window_xid(window.surface.window()?)?;
+    StackKeeper::start(xid, proxy.clone())
+        .map_err(|err| {
+            toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+        })
+        .ok()
+}
+
+fn start_visibility(
+    rules: &VisibilityConfig,
+    proxy: EventLoopProxy<OverlayEvent>,
+    toasts: &mut Toasts,
+) -> Option<VisibilityWatcher> {
+    VisibilityWatcher::start(rules, proxy).unwrap_or_else(|err| {
+        toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+        None
+    })
+}
+
+fn start_hotkeys(
+    config: &HotkeysConfig,
+    target: &EventLoopWindowTarget<OverlayEvent>,
+    proxy: EventLoopProxy<OverlayEvent>,
+    toasts: &mut Toasts,
+) -> Option<HotkeyWatcher> {
+    HotkeyWatcher::start(config, target, proxy).unwrap_or_else(|err| {
+        toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+        None
+    })
+}
+
+fn start_control(
+    config: &ControlConfig,
+    proxy: EventLoopProxy<OverlayEvent>,
+    toasts: &mut Toasts,
+) -> Option<ControlServer> {
+    ControlServer::start(config, proxy).unwrap_or_else(|err| {
+        toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+        None
+    })
+}
+
+/// `reads_frames` says whether any widget shows frame timings, which need a
+/// source.
+fn start_source(
+    config: &SourceConfig,
+    reads_frames: bool,
+    toasts: &mut Toasts,
+) -> Option<FrameSource> {
+    if *config == SourceConfig::None && reads_frames {
+        toasts.push(
+            ToastKind::Info,
+            "no [source] is set, so there are no frames to count; \
+             set [source] type = \"x11\" or \"hook\""
+                .to_owned(),
+            ERROR_TOAST_DURATION,
+        );
+    }
+    FrameSource::start(config).unwrap_or_else(|err| {
+        toasts.push(ToastKind::Error, format!("{err:#}"), ERROR_TOAST_DURATION);
+        None
+    })
+}
+
+fn place_on_monitor(window: &Window, monitor: &Monitor) {
+    let Some(monitor) = monitor.handle() else {
+        return;
+    };
+    let position: PhysicalPosition<i32> = monitor.position();
+    let size: PhysicalSize<u32> = monitor.size();
+    window.set_outer_position(position);
+    let _ = window.request_inner_size(size);
+}
+
+/// The monitor of each window to open; a single unplaced window when it
+/// follows another application's window instead.
+fn window_targets(config: &WindowConfig, monitors: &Monitors) -> Vec<Option<Monitor>> {
+    match config.follow {
+        Some(_) => vec![None],
+        None => monitors.targets(&config.monitor),
+    }
+}
+
+/// Puts each widget in `layout` that is not hidden on the windows its `monitor` selects, or on
+/// the first window when it selects none of them.
+fn assign_widgets(
+    windows: &mut [OverlayWindow],
+    widgets: &[WidgetSlot],
+    monitors: &Monitors,
+    layout: Option<&str>,
+) {
+    for window in windows.iter_mut() {
+        window.widgets.clear();
+    }
+    for (index, slot) in widgets.iter().enumerate() {
+        if slot.hidden || !slot.placement.in_layout(layout) {
+            continue;
+        }
+        let mut shown = false;
+        if let Some(selector) = &slot.placement.monitor {
+            for window in windows.iter_mut() {
+                if window
+                    .monitor
+                    .as_ref()
+                    .is_some_and(|monitor| monitors.matches(selector, monitor))
+                {
+                    window.widgets.push(index);
+                    shown = true;
+                }
+            }
+        }
+        if let Some(first) = windows.first_mut().filter(|_| !shown) {
+            first.widgets.push(index);
+        }
+    }
+}
+
+/// Input for a surface that gets no events: just its size and scale.
+fn fixed_input(size_in_points: egui::Vec2, pixels_per_point: f32) -> egui::RawInput {
+    let mut raw_input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, size_in_points)),
+        ..Default::default()
+    };
+    raw_input
+        .viewports
+        .entry(egui::ViewportId::ROOT)
+        .or_default()
+        .native_pixels_per_point = Some(pixels_per_point);
+    raw_input
+}
+
+/// One laid-out and tessellated frame, ready for any renderer.
+struct Frame {
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+    pixels_per_point: f32,
+    platform_output: egui::PlatformOutput,
+    repaint_delay: Option<Duration>,
+}
+
+/// Lays out the widgets at `shown` (indices into `widgets`) at `opacity`,
+/// plus `toasts` on the window that carries them.
+#[allow(clippy::too_many_arguments)]
+fn build_frame(
+    ctx: &egui::Context,
+    raw_input: egui::RawInput,
+    widgets: &mut [WidgetSlot],
+    shown: &[usize],
+    opacity: f32,
+    toasts: Option<&mut Toasts>,
+    mut edit: Option<&mut EditSession>,
+    now: Instant,
+) -> Frame {
+    let full_output = ctx.run(raw_input, |ctx| {
+        if let Some(edit) = edit.as_deref_mut() {
+            edit.begin_frame();
+            edit.paint_banner(ctx);
+        }
+        paint_widgets(ctx, widgets, shown, opacity, edit);
+        if let Some(toasts) = toasts {
+            toasts.paint(ctx, now);
+        }
+    });
+    let repaint_delay = full_output
+        .viewport_output
+        .get(&egui::ViewportId::ROOT)
+        .map(|viewport| viewport.repaint_delay);
+    Frame {
+        paint_jobs: ctx.tessellate(full_output.shapes, full_output.pixels_per_point),
+        textures_delta: full_output.textures_delta,
+        pixels_per_point: full_output.pixels_per_point,
+        platform_output: full_output.platform_output,
+        repaint_delay,
+    }
+}
+
+fn update_widgets(widgets: &mut [WidgetSlot], fps: &FpsTracker, metrics: &Metrics, now: Instant) {
+    let ctx = WidgetContext { fps, metrics, now };
+    for slot in widgets.iter_mut().filter(|slot| slot.is_due(now)) {
+        slot.widget.update(&ctx);
+        slot.last_update = Some(now);
+    }
+}
+
+/// In edit mode the widgets take the pointer, and `edit` moves and resizes
+/// them.
+fn paint_widgets(
+    ctx: &egui::Context,
+    widgets: &mut [WidgetSlot],
+    shown: &[usize],
+    opacity: f32,
+    mut edit: Option<&mut EditSession>,
+) {
+    for &index in shown {
+        let slot = &mut widgets[index];
+        let (align, offset) = slot.placement.anchor.to_egui(slot.placement.offset);
+        let area = egui::Area::new(egui::Id::new(("rs_overlay_widget", index)))
+            .anchor(align, offset)
+            .movable(false)
+            .interactable(edit.is_some())
+            .show(ctx, |ui| {
+                // Areas near the right edge would otherwise wrap their text.
+                ui.style_mut().wrap = Some(false);
+                ui.set_opacity(opacity);
+                slot.widget.paint(ui);
+                if let Some(edit) = edit.as_deref_mut() {
+                    edit.interact(ui, index, &mut slot.placement);
+                }
+            });
+        let rect = area.response.rect;
+        let transform = edit::scale_transform(align, rect, slot.placement.scale);
+        ctx.set_transform_layer(area.response.layer_id, transform);
+        if let Some(edit) = edit.as_deref_mut() {
+            edit.add_widget(transform * rect);
+        }
+    }
+}
@@ -53,10 +53,25 @@ mod unix {
     use x11rb::protocol::shape::ConnectionExt as _;
     use x11rb::{
         connection::Connection as _,
-        protocol::{shape, xproto},
+        protocol::{
+            shape,
+            xproto::{
+                self, AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, MapState,
+                PropMode,
+            },
+        },
         rust_connection::RustConnection,
+        wrapper::ConnectionExt as _,
     };
 
+    use crate::x11::Atoms;
+
+    /// `_NET_WM_DESKTOP` value for windows shown on every desktop.
+    const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+    const NET_WM_STATE_ADD: u32 = 1;
+    /// Source indication in EWMH client messages: a normal application.
+    const SOURCE_APPLICATION: u32 = 1;
+
     pub(super) fn configure(window: &winit::window::Window) {
         let handle = match window.window_handle() {
             Ok(handle) => handle,
@@ -66,11 +81,13 @@ mod unix {
         let window_id = match handle.as_raw() {
             RawWindowHandle::Xlib(handle) => handle.window as u32,
             RawWindowHandle::Xcb(handle) => handle.window.get(),
+            // Overlays on Wayland are layer surfaces where the compositor
+            // has them, which take no input to begin with.
             RawWindowHandle::Wayland(_) => return,
             _ => return,
         };
 
-        let (conn, _) = match RustConnection::connect(None) {
+        let (conn, screen) = match RustConnection::connect(None) {
             Ok(connection) => connection,
             Err(_) => return,
         };
@@ -84,11 +101,75 @@ mod unix {
             0,
             &[],
         );
+        let root = conn.setup().roots[screen].root;
+        set_wm_hints(&conn, root, window_id);
         let _ = conn.flush();
     }
+
+    /// Keeps the window off taskbars and pagers and on every desktop. The
+    /// window manager reads the properties when the window is mapped, and
+    /// takes client messages once it is.
+    fn set_wm_hints(conn: &RustConnection, root: xproto::Window, window: xproto::Window) {
+        let Some(atoms) = Atoms::new(conn).ok().and_then(|cookie| cookie.reply().ok()) else {
+            return;
+        };
+        let mapped = conn
+            .get_window_attributes(window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|attributes| attributes.map_state != MapState::UNMAPPED);
+        let states = [
+            atoms._NET_WM_STATE_STICKY,
+            atoms._NET_WM_STATE_SKIP_TASKBAR,
+            atoms._NET_WM_STATE_SKIP_PAGER,
+        ];
+
+        if !mapped {
+            let _ = conn.change_property32(
+                PropMode::APPEND,
+                window,
+                atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                &states,
+            );
+            let _ = conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                atoms._NET_WM_DESKTOP,
+                AtomEnum::CARDINAL,
+                &[ALL_DESKTOPS],
+            );
+            return;
+        }
+
+        let mask = EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY;
+        // Each message adds up to two states.
+        for pair in states.chunks(2) {
+            let message = ClientMessageEvent::new(
+                32,
+                window,
+                atoms._NET_WM_STATE,
+                [
+                    NET_WM_STATE_ADD,
+                    pair[0],
+                    pair.get(1).copied().unwrap_or(x11rb::NONE),
+                    SOURCE_APPLICATION,
+                    0,
+                ],
+            );
+            let _ = conn.send_event(false, root, mask, message);
+        }
+        let message = ClientMessageEvent::new(
+            32,
+            window,
+            atoms._NET_WM_DESKTOP,
+            [ALL_DESKTOPS, SOURCE_APPLICATION, 0, 0, 0],
+        );
+        let _ = conn.send_event(false, root, mask, message);
+    }
 }
 
-pub fn configure_overlay(window: &winit::window::Window) {
+pub(crate) fn configure_overlay(window: &winit::window::Window) {
     #[cfg(windows)]
     {
         windows::configure(window);
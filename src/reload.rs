@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use notify::{RecursiveMode, Watcher as _};
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::OverlayEvent;
+
+/// Keeps an inotify watch on the directory holding the config file alive.
+///
+/// The directory rather than the file is watched because editors usually
+/// save by writing a temporary file and renaming it over the original, which
+/// would silently drop a watch placed on the old inode.
+pub(crate) struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub(crate) fn new(path: &Path, proxy: EventLoopProxy<OverlayEvent>) -> anyhow::Result<Self> {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("resolve {}", path.display()))?;
+        let dir = path
+            .parent()
+            .map(Path::to_owned)
+            .unwrap_or_else(|| PathBuf::from("."));
+        let file_name = path.file_name().map(ToOwned::to_owned);
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else { return };
+                if !(event.kind.is_create() || event.kind.is_modify()) {
+                    return;
+                }
+                let touches_config = event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref());
+                if touches_config {
+                    let _ = proxy.send_event(OverlayEvent::ConfigChanged);
+                }
+            })
+            .context("create config watcher")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("watch {}", dir.display()))?;
+
+        Ok(Self { _watcher: watcher })
+    }
+}
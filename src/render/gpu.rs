@@ -0,0 +1,203 @@
+use anyhow::Context as _;
+
+use super::RenderTarget;
+
+pub(crate) struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
+    renderer: egui_wgpu::Renderer,
+}
+
+impl GpuRenderer {
+    pub(crate) async fn new(
+        target: RenderTarget,
+        present_mode: wgpu::PresentMode,
+    ) -> anyhow::Result<Self> {
+        let size = target.size();
+        let instance = wgpu::Instance::default();
+        let surface = match target {
+            RenderTarget::Window(window) => instance.create_surface(window),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            RenderTarget::Layer(handle) => instance.create_surface(handle),
+        }
+        .context("create surface")?;
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await
+            .context("find adapter")?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await
+            .context("create device")?;
+        let surface_caps = surface.get_capabilities(&adapter);
+        // egui blends in gamma space, like the CPU rasterizer, which only
+        // matches on a surface that does not convert to sRGB on write.
+        let surface_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|format| !format.is_srgb())
+            .or_else(|| surface_caps.formats.first().copied())
+            .context("the surface supports no formats")?;
+        let surface_alpha_mode = pick_alpha_mode(&surface_caps.alpha_modes);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: supported_present_mode(&surface_caps.present_modes, present_mode),
+            alpha_mode: surface_alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &config);
+        let renderer = egui_wgpu::Renderer::new(&device, config.format, None, 1);
+
+        Ok(Self {
+            device,
+            queue,
+            surface,
+            config,
+            present_modes: surface_caps.present_modes,
+            renderer,
+        })
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.config.width = width;
+            self.config.height = height;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
+    pub(crate) fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        let present_mode = supported_present_mode(&self.present_modes, present_mode);
+        if self.config.present_mode != present_mode {
+            self.config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Returns `false` when the surface can no longer be rendered to.
+    pub(crate) fn paint(
+        &mut self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        pixels_per_point: f32,
+    ) -> bool {
+        match self.try_paint(paint_jobs, textures_delta, pixels_per_point) {
+            Ok(()) => true,
+            Err(wgpu::SurfaceError::Lost) => {
+                self.surface.configure(&self.device, &self.config);
+                true
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => false,
+            Err(_) => true,
+        }
+    }
+
+    fn try_paint(
+        &mut self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        pixels_per_point: f32,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let renderer = &mut self.renderer;
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point,
+        };
+
+        for (id, image_delta) in &textures_delta.set {
+            renderer.update_texture(&self.device, &self.queue, *id, image_delta);
+        }
+
+        for id in &textures_delta.free {
+            renderer.free_texture(id);
+        }
+
+        let output_frame = self.surface.get_current_texture()?;
+        let view = output_frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            paint_jobs,
+            &screen_descriptor,
+        );
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.0,
+                            g: 0.0,
+                            b: 0.0,
+                            a: 0.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            renderer.render(&mut rpass, paint_jobs, &screen_descriptor);
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        output_frame.present();
+        Ok(())
+    }
+}
+
+/// Falls back to `Fifo`, which every surface supports, when `requested` is
+/// not available.
+fn supported_present_mode(
+    modes: &[wgpu::PresentMode],
+    requested: wgpu::PresentMode,
+) -> wgpu::PresentMode {
+    if modes.contains(&requested) {
+        requested
+    } else {
+        wgpu::PresentMode::Fifo
+    }
+}
+
+fn pick_alpha_mode(modes: &[wgpu::CompositeAlphaMode]) -> wgpu::CompositeAlphaMode {
+    let preferred = [
+        wgpu::CompositeAlphaMode::PreMultiplied,
+        wgpu::CompositeAlphaMode::PostMultiplied,
+        wgpu::CompositeAlphaMode::Inherit,
+        wgpu::CompositeAlphaMode::Opaque,
+    ];
+
+    preferred
+        .into_iter()
+        .find(|mode| modes.contains(mode))
+        .unwrap_or(wgpu::CompositeAlphaMode::Opaque)
+}
@@ -0,0 +1,97 @@
+mod gpu;
+mod raster;
+mod software;
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use winit::{dpi::PhysicalSize, window::Window};
+
+use crate::config::{RendererBackend, RendererConfig};
+
+pub(crate) use gpu::GpuRenderer;
+pub(crate) use raster::{Canvas, Rasterizer};
+pub(crate) use software::SoftwareRenderer;
+
+/// What a renderer draws into.
+#[derive(Clone)]
+pub(crate) enum RenderTarget {
+    Window(Arc<Window>),
+    #[cfg(all(unix, not(target_os = "macos")))]
+    Layer(crate::wayland::SurfaceHandle),
+}
+
+impl RenderTarget {
+    fn size(&self) -> PhysicalSize<u32> {
+        match self {
+            Self::Window(window) => window.inner_size(),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            Self::Layer(handle) => handle.size(),
+        }
+    }
+}
+
+/// Draws tessellated egui output into the overlay window.
+pub(crate) enum Renderer {
+    Gpu(Box<GpuRenderer>),
+    Software(Box<SoftwareRenderer>),
+}
+
+impl Renderer {
+    /// Creates the backend `config` asks for. With `auto`, a missing or
+    /// broken GPU falls back to the software renderer and the reason goes to
+    /// `on_fallback`.
+    pub(crate) async fn new(
+        target: RenderTarget,
+        config: &RendererConfig,
+        on_fallback: impl FnOnce(String),
+    ) -> anyhow::Result<Self> {
+        let present_mode = config.present_mode.into();
+        match config.backend {
+            RendererBackend::Gpu => Ok(Self::Gpu(Box::new(
+                GpuRenderer::new(target, present_mode).await?,
+            ))),
+            RendererBackend::Software => {
+                Ok(Self::Software(Box::new(SoftwareRenderer::new(&target)?)))
+            }
+            RendererBackend::Auto => match GpuRenderer::new(target.clone(), present_mode).await {
+                Ok(gpu) => Ok(Self::Gpu(Box::new(gpu))),
+                Err(err) => {
+                    let software = SoftwareRenderer::new(&target).with_context(|| {
+                        format!("{err:#}; the software renderer is unavailable too")
+                    })?;
+                    on_fallback(format!("{err:#}; using the software renderer"));
+                    Ok(Self::Software(Box::new(software)))
+                }
+            },
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        match self {
+            Self::Gpu(gpu) => gpu.resize(width, height),
+            Self::Software(software) => software.resize(width, height),
+        }
+    }
+
+    pub(crate) fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        if let Self::Gpu(gpu) = self {
+            gpu.set_present_mode(present_mode);
+        }
+    }
+
+    /// Returns `false` when the window can no longer be drawn to.
+    pub(crate) fn paint(
+        &mut self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        pixels_per_point: f32,
+    ) -> bool {
+        match self {
+            Self::Gpu(gpu) => gpu.paint(paint_jobs, textures_delta, pixels_per_point),
+            Self::Software(software) => {
+                software.paint(paint_jobs, textures_delta, pixels_per_point)
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use egui::{Color32, epaint::Primitive};
+
+/// Premultiplied RGBA pixels, row by row from the top-left corner.
+pub(crate) struct Canvas {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<Color32>,
+}
+
+impl Canvas {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color32::TRANSPARENT; width as usize * height as usize],
+        }
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.pixels
+            .resize(width as usize * height as usize, Color32::TRANSPARENT);
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill(Color32::TRANSPARENT);
+    }
+
+    fn blend(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let pixel = &mut self.pixels[y as usize * self.width as usize + x as usize];
+        let dst = pixel.to_array();
+        let keep = 1.0 - src[3] / 255.0;
+        let channel = |index: usize| (src[index] + dst[index] as f32 * keep).round() as u8;
+        *pixel = Color32::from_rgba_premultiplied(channel(0), channel(1), channel(2), channel(3));
+    }
+}
+
+struct Texture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color32>,
+    filter: egui::TextureFilter,
+}
+
+impl Texture {
+    /// Premultiplied texel at normalized `uv`, clamped to the edges.
+    fn sample(&self, uv: [f32; 2]) -> [f32; 4] {
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = uv[1] * self.height as f32 - 0.5;
+        match self.filter {
+            egui::TextureFilter::Nearest => self.texel(x.round() as isize, y.round() as isize),
+            egui::TextureFilter::Linear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = mix(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+                let bottom = mix(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+                mix(top, bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: isize, y: isize) -> [f32; 4] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x].to_array().map(f32::from)
+    }
+}
+
+/// Draws tessellated egui output on the CPU, for machines without a usable
+/// GPU and for rendering to images.
+///
+/// Blending happens in gamma space on premultiplied colors, like egui's own
+/// GPU backends on a non-sRGB surface.
+#[derive(Default)]
+pub(crate) struct Rasterizer {
+    textures: HashMap<egui::TextureId, Texture>,
+}
+
+impl Rasterizer {
+    pub(crate) fn update_textures(&mut self, delta: &egui::TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let [width, height] = image_delta.image.size();
+            let pixels: Vec<Color32> = match &image_delta.image {
+                egui::ImageData::Color(image) => image.pixels.clone(),
+                egui::ImageData::Font(image) => image.srgba_pixels(None).collect(),
+            };
+            let filter = image_delta.options.magnification;
+            match image_delta.pos {
+                None => {
+                    self.textures.insert(
+                        *id,
+                        Texture {
+                            width,
+                            height,
+                            pixels,
+                            filter,
+                        },
+                    );
+                }
+                Some([left, top]) => {
+                    let Some(texture) = self.textures.get_mut(id) else {
+                        continue;
+                    };
+                    for (row, source) in pixels.chunks_exact(width).enumerate() {
+                        let start = (top + row) * texture.width + left;
+                        texture.pixels[start..start + width].copy_from_slice(source);
+                    }
+                }
+            }
+        }
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Clears `canvas` and draws `paint_jobs` onto it.
+    pub(crate) fn paint(
+        &self,
+        canvas: &mut Canvas,
+        paint_jobs: &[egui::ClippedPrimitive],
+        pixels_per_point: f32,
+    ) {
+        self.paint_region(canvas, paint_jobs, pixels_per_point, egui::Vec2::ZERO);
+    }
+
+    /// Like `paint`, for a `canvas` covering only the part of the frame
+    /// from pixel `origin` on.
+    pub(crate) fn paint_region(
+        &self,
+        canvas: &mut Canvas,
+        paint_jobs: &[egui::ClippedPrimitive],
+        pixels_per_point: f32,
+        origin: egui::Vec2,
+    ) {
+        canvas.clear();
+        for job in paint_jobs {
+            let Primitive::Mesh(mesh) = &job.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = PixelRect::from_clip(job.clip_rect, pixels_per_point, origin, canvas);
+            if clip.is_empty() {
+                continue;
+            }
+            for triangle in mesh.indices.chunks_exact(3) {
+                let vertices = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+                fill_triangle(canvas, clip, texture, vertices, pixels_per_point, origin);
+            }
+        }
+    }
+}
+
+/// Half-open pixel bounds.
+#[derive(Clone, Copy)]
+struct PixelRect {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl PixelRect {
+    fn from_clip(
+        clip: egui::Rect,
+        pixels_per_point: f32,
+        origin: egui::Vec2,
+        canvas: &Canvas,
+    ) -> Self {
+        let to_pixel = |points: f32, origin: f32, limit: u32| {
+            (points * pixels_per_point - origin).clamp(0.0, limit as f32)
+        };
+        Self {
+            left: to_pixel(clip.min.x, origin.x, canvas.width).floor() as u32,
+            top: to_pixel(clip.min.y, origin.y, canvas.height).floor() as u32,
+            right: to_pixel(clip.max.x, origin.x, canvas.width).ceil() as u32,
+            bottom: to_pixel(clip.max.y, origin.y, canvas.height).ceil() as u32,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.left >= self.right || self.top >= self.bottom
+    }
+}
+
+fn fill_triangle(
+    canvas: &mut Canvas,
+    clip: PixelRect,
+    texture: &Texture,
+    mut vertices: [&egui::epaint::Vertex; 3],
+    pixels_per_point: f32,
+    origin: egui::Vec2,
+) {
+    let mut points = vertices.map(|vertex| {
+        [
+            vertex.pos.x * pixels_per_point - origin.x,
+            vertex.pos.y * pixels_per_point - origin.y,
+        ]
+    });
+    let mut area = edge(points[0], points[1], points[2]);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    // Wind every triangle the same way so the fill rule below treats shared
+    // edges consistently.
+    if area < 0.0 {
+        vertices.swap(1, 2);
+        points.swap(1, 2);
+        area = -area;
+    }
+
+    let xs = points.map(|point| point[0]);
+    let ys = points.map(|point| point[1]);
+    let left = (xs.into_iter().fold(f32::INFINITY, f32::min).floor() as i64).max(clip.left as i64);
+    let top = (ys.into_iter().fold(f32::INFINITY, f32::min).floor() as i64).max(clip.top as i64);
+    let right =
+        (xs.into_iter().fold(f32::NEG_INFINITY, f32::max).ceil() as i64).min(clip.right as i64);
+    let bottom =
+        (ys.into_iter().fold(f32::NEG_INFINITY, f32::max).ceil() as i64).min(clip.bottom as i64);
+
+    let edges = [[1, 2], [2, 0], [0, 1]].map(|[from, to]| (points[from], points[to]));
+    let colors = vertices.map(|vertex| vertex.color.to_array().map(f32::from));
+    let uvs = vertices.map(|vertex| [vertex.uv.x, vertex.uv.y]);
+
+    for y in top..bottom {
+        for x in left..right {
+            let center = [x as f32 + 0.5, y as f32 + 0.5];
+            let mut weights = [0.0; 3];
+            let mut inside = true;
+            for (weight, &(from, to)) in weights.iter_mut().zip(&edges) {
+                let value = edge(from, to, center);
+                if value < 0.0 || (value == 0.0 && !owns_edge(from, to)) {
+                    inside = false;
+                    break;
+                }
+                *weight = value / area;
+            }
+            if !inside {
+                continue;
+            }
+
+            let color = interpolate(&colors, weights);
+            let uv = interpolate(&uvs, weights);
+            let texel = texture.sample(uv);
+            let src = [0, 1, 2, 3].map(|index| color[index] * texel[index] / 255.0);
+            if src[3] > 0.0 {
+                canvas.blend(x as u32, y as u32, src);
+            }
+        }
+    }
+}
+
+/// Twice the signed area of `a`, `b`, `c`; positive when `c` is clockwise
+/// from `a`→`b` on screen.
+fn edge(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Tie-break for pixel centers exactly on an edge: of the two triangles
+/// sharing it, only the one that walks it in the owning direction draws them.
+fn owns_edge(from: [f32; 2], to: [f32; 2]) -> bool {
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    dy > 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+fn interpolate<const N: usize>(values: &[[f32; N]; 3], weights: [f32; 3]) -> [f32; N] {
+    std::array::from_fn(|index| {
+        values[0][index] * weights[0]
+            + values[1][index] * weights[1]
+            + values[2][index] * weights[2]
+    })
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    std::array::from_fn(|index| a[index] + (b[index] - a[index]) * t)
+}
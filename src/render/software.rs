@@ -0,0 +1,55 @@
+use super::{Canvas, Rasterizer, RenderTarget};
+
+/// Rasterizes on the CPU and copies each frame into the window.
+pub(crate) struct SoftwareRenderer {
+    rasterizer: Rasterizer,
+    canvas: Canvas,
+    #[cfg(all(unix, not(target_os = "macos")))]
+    blitter: crate::x11::WindowBlitter,
+}
+
+impl SoftwareRenderer {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub(crate) fn new(target: &RenderTarget) -> anyhow::Result<Self> {
+        let RenderTarget::Window(window) = target else {
+            anyhow::bail!("the software renderer needs an X11 window");
+        };
+        let xid = crate::x11::window_xid(window)
+            .ok_or_else(|| anyhow::anyhow!("the software renderer needs an X11 window"))?;
+        let size = window.inner_size();
+        Ok(Self {
+            rasterizer: Rasterizer::default(),
+            canvas: Canvas::new(size.width, size.height),
+            blitter: crate::x11::WindowBlitter::new(xid)?,
+        })
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub(crate) fn new(_target: &RenderTarget) -> anyhow::Result<Self> {
+        anyhow::bail!("the software renderer needs X11")
+    }
+
+    pub(crate) fn resize(&mut self, width: u32, height: u32) {
+        self.canvas.resize(width, height);
+    }
+
+    /// Returns `false` when the window can no longer be drawn to.
+    pub(crate) fn paint(
+        &mut self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        textures_delta: &egui::TexturesDelta,
+        pixels_per_point: f32,
+    ) -> bool {
+        self.rasterizer.update_textures(textures_delta);
+        self.rasterizer
+            .paint(&mut self.canvas, paint_jobs, pixels_per_point);
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            self.blitter.present(&self.canvas).is_ok()
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            false
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+/// Longest the overlay sleeps even when nothing asked for a redraw, so status
+/// messages from frame sources still show up.
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+
+/// Decides when the overlay next redraws so the event loop can sleep in
+/// between instead of polling.
+pub(crate) struct RedrawSchedule {
+    min_interval: Option<Duration>,
+    last_redraw: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl RedrawSchedule {
+    pub(crate) fn new(min_interval: Option<Duration>) -> Self {
+        Self {
+            min_interval,
+            last_redraw: None,
+            deadline: None,
+        }
+    }
+
+    pub(crate) fn set_min_interval(&mut self, min_interval: Option<Duration>) {
+        self.min_interval = min_interval;
+    }
+
+    /// Asks for a redraw no later than `at`, frame rate cap permitting.
+    pub(crate) fn request(&mut self, at: Instant) {
+        self.deadline = Some(self.deadline.map_or(at, |deadline| deadline.min(at)));
+    }
+
+    /// Clears all requests; the caller re-requests what the new frame needs.
+    pub(crate) fn redrawn(&mut self, now: Instant) {
+        self.last_redraw = Some(now);
+        self.deadline = None;
+    }
+
+    /// When the next redraw is due. Never earlier than the cap allows after
+    /// the previous one.
+    pub(crate) fn next(&self, now: Instant) -> Instant {
+        let due = self.deadline.unwrap_or(now + IDLE_REDRAW);
+        match (self.last_redraw, self.min_interval) {
+            (Some(last), Some(min_interval)) => due.max(last + min_interval),
+            _ => due,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs::File,
+    io::Read as _,
+    os::fd::{AsFd as _, AsRawFd as _, BorrowedFd, FromRawFd as _},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use winit::event_loop::EventLoopProxy;
+
+use crate::event::OverlayEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Write end of the pipe the handler wakes the watcher thread through; `-1`
+/// while no watcher runs.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Turns `SIGUSR1` into `OverlayEvent::ToggleEditMode`, so
+/// `pkill -USR1 rs_overlay` toggles edit mode. Dropping it stops the thread
+/// and restores the default action.
+pub(crate) struct SignalWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl SignalWatcher {
+    pub(crate) fn start(proxy: EventLoopProxy<OverlayEvent>) -> anyhow::Result<Self> {
+        let mut fds = [0; 2];
+        // Non-blocking, so a handler never waits on a full pipe.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("create signal pipe");
+        }
+        let [read, write] = fds.map(|fd| unsafe { File::from_raw_fd(fd) });
+        PIPE.store(write.as_raw_fd(), Ordering::Relaxed);
+        unsafe {
+            libc::signal(
+                libc::SIGUSR1,
+                on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        thread::Builder::new()
+            .name("rs_overlay-signals".to_owned())
+            .spawn(move || {
+                run(read, &proxy, &stop_thread);
+                unsafe {
+                    libc::signal(libc::SIGUSR1, libc::SIG_DFL);
+                }
+                PIPE.store(-1, Ordering::Relaxed);
+                drop(write);
+            })
+            .context("spawn signal watcher")?;
+        Ok(Self { stop })
+    }
+}
+
+impl Drop for SignalWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn run(mut read: File, proxy: &EventLoopProxy<OverlayEvent>, stop: &AtomicBool) {
+    let mut buffer = [0; 16];
+    while !stop.load(Ordering::Relaxed) {
+        if !wait_readable(read.as_fd(), POLL_INTERVAL) {
+            continue;
+        }
+        // One toggle per wakeup; signals sent in a burst collapse anyway.
+        match read.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => {
+                if proxy.send_event(OverlayEvent::ToggleEditMode).is_err() {
+                    break;
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(_) => break,
+        }
+    }
+}
+
+fn wait_readable(fd: BorrowedFd<'_>, timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    unsafe { libc::poll(&mut fd, 1, timeout_ms) > 0 }
+}
+
+/// Only does what is async-signal-safe: a single `write`.
+extern "C" fn on_signal(_: libc::c_int) {
+    let fd = PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        unsafe {
+            libc::write(fd, [1u8].as_ptr().cast(), 1);
+        }
+    }
+}
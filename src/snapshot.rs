@@ -0,0 +1,221 @@
+//! Offscreen renders of the overlay and comparison against golden images, so
+//! layout changes can be checked without a compositor.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+
+/// Set to `1` to rewrite golden images instead of comparing against them.
+pub const UPDATE_ENV: &str = "RS_OVERLAY_UPDATE_SNAPSHOTS";
+
+/// A rendered frame with straight (not premultiplied) RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Snapshot {
+    pub(crate) fn from_premultiplied(width: u32, height: u32, pixels: &[egui::Color32]) -> Self {
+        // The rasterizer blends in gamma space, so unmultiply there too.
+        let rgba = pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [r, g, b, a] = pixel.to_array();
+                let unmultiply = |channel: u8| match a {
+                    0 => 0,
+                    _ => ((channel as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8,
+                };
+                [unmultiply(r), unmultiply(g), unmultiply(b), a]
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    pub fn load_png(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::ALPHA);
+        let mut reader = decoder
+            .read_info()
+            .with_context(|| format!("read {}", path.display()))?;
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut rgba)
+            .with_context(|| format!("decode {}", path.display()))?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            anyhow::bail!("{} is not an 8-bit RGBA image", path.display());
+        }
+        rgba.truncate(info.buffer_size());
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            rgba,
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer
+            .write_image_data(&self.rgba)
+            .with_context(|| format!("write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// How far `self` is from `expected`. Images of different sizes differ
+    /// in every pixel.
+    pub fn compare(&self, expected: &Self) -> Difference {
+        let pixels = self.width as usize * self.height as usize;
+        if self.width != expected.width || self.height != expected.height {
+            return Difference {
+                pixels,
+                fraction: 1.0,
+                max_channel: u8::MAX,
+            };
+        }
+        let mut differing = 0;
+        let mut max_channel = 0;
+        for (actual, expected) in self.rgba.chunks_exact(4).zip(expected.rgba.chunks_exact(4)) {
+            let channel = actual
+                .iter()
+                .zip(expected)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or(0);
+            if channel > 0 {
+                differing += 1;
+                max_channel = max_channel.max(channel);
+            }
+        }
+        Difference {
+            pixels: differing,
+            fraction: differing as f32 / pixels.max(1) as f32,
+            max_channel,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difference {
+    /// Pixels with any channel off by at least one.
+    pub pixels: usize,
+    pub fraction: f32,
+    /// Largest difference in any channel of any pixel.
+    pub max_channel: u8,
+}
+
+/// How much a render may drift from its golden image, e.g. from rounding
+/// differences between platforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Channel differences up to this much are ignored.
+    pub max_channel: u8,
+    /// Fraction of pixels allowed to exceed `max_channel`.
+    pub max_fraction: f32,
+}
+
+impl Tolerance {
+    pub fn accepts(&self, actual: &Snapshot, expected: &Snapshot) -> bool {
+        if actual.width != expected.width || actual.height != expected.height {
+            return false;
+        }
+        let exceeding = actual
+            .rgba
+            .chunks_exact(4)
+            .zip(expected.rgba.chunks_exact(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(*b)
+                    .any(|(a, b)| a.abs_diff(*b) > self.max_channel)
+            })
+            .count();
+        exceeding as f32 <= self.max_fraction * (actual.width * actual.height) as f32
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_channel: 2,
+            max_fraction: 0.001,
+        }
+    }
+}
+
+/// Compares `actual` against the PNG at `golden` and panics with a summary
+/// when they differ by more than `tolerance`. The render is then saved next
+/// to the golden image as `<name>.actual.png` for inspection.
+///
+/// With [`UPDATE_ENV`] set to `1`, the render is written as the golden
+/// image instead. A missing golden image fails otherwise, so a clean checkout
+/// cannot accept whatever it renders.
+pub fn assert_matches_golden(actual: &Snapshot, golden: impl AsRef<Path>, tolerance: Tolerance) {
+    let golden = golden.as_ref();
+    let update = std::env::var(UPDATE_ENV).is_ok_and(|value| value == "1");
+    if !update && !golden.exists() {
+        let saved = save_actual(actual, golden);
+        panic!(
+            "no golden image at {}; {saved}; rerun with {UPDATE_ENV}=1 to create it",
+            golden.display()
+        );
+    }
+    if update {
+        if let Some(parent) = golden.parent() {
+            std::fs::create_dir_all(parent)
+                .unwrap_or_else(|err| panic!("create {}: {err}", parent.display()));
+        }
+        actual
+            .save_png(golden)
+            .unwrap_or_else(|err| panic!("{err:#}"));
+        return;
+    }
+
+    let expected = Snapshot::load_png(golden).unwrap_or_else(|err| panic!("{err:#}"));
+    if tolerance.accepts(actual, &expected) {
+        return;
+    }
+    let saved = save_actual(actual, golden);
+    let difference = actual.compare(&expected);
+    panic!(
+        "snapshot differs from {}: {}x{} vs {}x{}, {} pixels ({:.3}%) differ, by up to {}; {saved}; \
+         rerun with {UPDATE_ENV}=1 to accept it",
+        golden.display(),
+        actual.width,
+        actual.height,
+        expected.width,
+        expected.height,
+        difference.pixels,
+        difference.fraction * 100.0,
+        difference.max_channel,
+    );
+}
+
+/// Saves `actual` next to `golden` and says where, for the panic message.
+fn save_actual(actual: &Snapshot, golden: &Path) -> String {
+    let actual_path = actual_path(golden);
+    match actual.save_png(&actual_path) {
+        Ok(()) => format!("render saved to {}", actual_path.display()),
+        Err(err) => format!("could not save the render: {err:#}"),
+    }
+}
+
+fn actual_path(golden: &Path) -> PathBuf {
+    let stem = golden
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    golden.with_file_name(format!("{stem}.actual.png"))
+}
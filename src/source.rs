@@ -0,0 +1,90 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{FpsTracker, config::SourceConfig};
+
+pub(crate) enum SourceEvent {
+    Frame(Instant),
+    /// Something worth telling the user, such as which window is measured.
+    Status(String),
+}
+
+/// Frame timings produced outside the overlay's own loop. Dropping it stops
+/// the background thread feeding it.
+pub(crate) struct FrameSource {
+    events: Receiver<SourceEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl FrameSource {
+    /// `None` without a source to count frames of.
+    pub(crate) fn start(config: &SourceConfig) -> anyhow::Result<Option<Self>> {
+        match config {
+            SourceConfig::None => Ok(None),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            SourceConfig::X11(options) => {
+                if options.window.is_empty() {
+                    anyhow::bail!("[source] type = \"x11\" needs a pid, class or title");
+                }
+                let (sender, events) = mpsc::channel();
+                let stop = Arc::new(AtomicBool::new(false));
+                crate::x11::spawn_frame_counter(options.clone(), sender, stop.clone())?;
+                Ok(Some(Self { events, stop }))
+            }
+            #[cfg(all(unix, not(target_os = "macos")))]
+            SourceConfig::Hook(options) => {
+                let (sender, events) = mpsc::channel();
+                let stop = Arc::new(AtomicBool::new(false));
+                crate::hook::spawn_hook_reader(options.clone(), sender, stop.clone())?;
+                Ok(Some(Self { events, stop }))
+            }
+            #[cfg(not(all(unix, not(target_os = "macos"))))]
+            SourceConfig::X11(_) => anyhow::bail!("the x11 frame source needs an X11 session"),
+            #[cfg(not(all(unix, not(target_os = "macos"))))]
+            SourceConfig::Hook(_) => anyhow::bail!("the hook frame source is only built for Linux"),
+        }
+    }
+
+    /// Records pending frames into `tracker` and hands status messages to
+    /// `on_status`.
+    pub(crate) fn drain(&self, tracker: &mut FpsTracker, mut on_status: impl FnMut(String)) {
+        for event in self.events.try_iter() {
+            match event {
+                SourceEvent::Frame(at) => tracker.record(at),
+                SourceEvent::Status(message) => on_status(message),
+            }
+        }
+    }
+}
+
+impl Drop for FrameSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Converts a `CLOCK_MONOTONIC` reading, as used by the Present extension's
+/// `ust` and by the hooks, into an `Instant`. Zero means the producer had no
+/// timestamp and maps to now.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn instant_from_monotonic(since_boot: Duration) -> Instant {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let now_instant = Instant::now();
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 || since_boot.is_zero()
+    {
+        return now_instant;
+    }
+    let now_since_boot = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+    now_instant
+        .checked_sub(now_since_boot.saturating_sub(since_boot))
+        .unwrap_or(now_instant)
+}
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ToastKind {
+    Info,
+    Error,
+}
+
+/// Short-lived messages shown at the bottom of the overlay, such as config
+/// errors that would otherwise only be visible on a terminal nobody watches.
+#[derive(Default)]
+pub(crate) struct Toasts {
+    entries: Vec<Toast>,
+}
+
+struct Toast {
+    kind: ToastKind,
+    message: String,
+    expires: Instant,
+}
+
+impl Toasts {
+    /// A message already on screen is kept up longer rather than repeated,
+    /// e.g. when every overlay window reports the same renderer fallback.
+    pub(crate) fn push(&mut self, kind: ToastKind, message: impl Into<String>, duration: Duration) {
+        let message = message.into();
+        let expires = Instant::now() + duration;
+        match self
+            .entries
+            .iter_mut()
+            .find(|toast| toast.kind == kind && toast.message == message)
+        {
+            Some(toast) => toast.expires = toast.expires.max(expires),
+            None => self.entries.push(Toast {
+                kind,
+                message,
+                expires,
+            }),
+        }
+    }
+
+    /// When the next visible toast disappears, which needs a redraw.
+    pub(crate) fn next_expiry(&self) -> Option<Instant> {
+        self.entries.iter().map(|toast| toast.expires).min()
+    }
+
+    pub(crate) fn paint(&mut self, ctx: &egui::Context, now: Instant) {
+        self.entries.retain(|toast| toast.expires > now);
+        if self.entries.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("rs_overlay_toasts"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -24.0))
+            .show(ctx, |ui| {
+                for toast in &self.entries {
+                    let fill = match toast.kind {
+                        ToastKind::Info => egui::Color32::from_rgba_unmultiplied(30, 30, 30, 220),
+                        ToastKind::Error => egui::Color32::from_rgba_unmultiplied(120, 20, 20, 220),
+                    };
+                    egui::Frame::none()
+                        .fill(fill)
+                        .rounding(4.0)
+                        .inner_margin(egui::Margin::symmetric(10.0, 6.0))
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(&toast.message).color(egui::Color32::WHITE),
+                            );
+                        });
+                }
+            });
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::{config::VisibilityConfig, event::OverlayEvent};
+
+/// Widget opacity, easing between hidden and shown.
+pub(crate) struct Fade {
+    from: f32,
+    to: f32,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Fade {
+    pub(crate) fn new(shown: bool, duration: Duration) -> Self {
+        let opacity = if shown { 1.0 } else { 0.0 };
+        Self {
+            from: opacity,
+            to: opacity,
+            started: Instant::now(),
+            duration,
+        }
+    }
+
+    /// Starts fading from the current opacity towards `shown`.
+    pub(crate) fn set(&mut self, shown: bool, duration: Duration, now: Instant) {
+        let to = if shown { 1.0 } else { 0.0 };
+        self.from = self.opacity(now);
+        self.to = to;
+        self.started = now;
+        self.duration = duration;
+    }
+
+    pub(crate) fn opacity(&self, now: Instant) -> f32 {
+        let progress = match self.duration.as_secs_f32() {
+            0.0 => 1.0,
+            duration => {
+                (now.saturating_duration_since(self.started).as_secs_f32() / duration).min(1.0)
+            }
+        };
+        self.from + (self.to - self.from) * progress
+    }
+
+    pub(crate) fn is_fading(&self, now: Instant) -> bool {
+        self.opacity(now) != self.to
+    }
+}
+
+/// Evaluates `[visibility]` rules in the background and reports each change
+/// as `OverlayEvent::Visibility`. Dropping it stops the thread.
+pub(crate) struct VisibilityWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl VisibilityWatcher {
+    /// `None` when there are no rules, so the widgets are always shown.
+    pub(crate) fn start(
+        rules: &VisibilityConfig,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            let stop = Arc::new(AtomicBool::new(false));
+            linux::spawn(rules.clone(), proxy, stop.clone())?;
+            Ok(Some(Self { stop }))
+        }
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            let _ = proxy;
+            anyhow::bail!("[visibility] rules are only supported on Linux")
+        }
+    }
+}
+
+impl Drop for VisibilityWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod linux {
+    use std::{
+        fs,
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::Context as _;
+    use winit::event_loop::EventLoopProxy;
+
+    use crate::{
+        config::{Pattern, VisibilityConfig},
+        event::OverlayEvent,
+        x11::ActiveWindow,
+    };
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+    /// Walking `/proc` is comparatively slow, so processes are checked less
+    /// often than the focus.
+    const PROCESS_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub(super) fn spawn(
+        rules: VisibilityConfig,
+        proxy: EventLoopProxy<OverlayEvent>,
+        stop: Arc<AtomicBool>,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        let active = match rules.needs_focus() {
+            true => Some(ActiveWindow::connect()?),
+            false => None,
+        };
+        thread::Builder::new()
+            .name("rs_overlay-visibility".to_owned())
+            .spawn(move || {
+                if let Err(err) = run(&rules, active, &proxy, &stop) {
+                    let _ = proxy
+                        .send_event(OverlayEvent::Status(format!("[visibility] rules: {err:#}")));
+                }
+            })
+            .context("spawn visibility watcher")
+    }
+
+    fn run(
+        rules: &VisibilityConfig,
+        mut active: Option<ActiveWindow>,
+        proxy: &EventLoopProxy<OverlayEvent>,
+        stop: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let mut focus_holds = true;
+        let mut focus_changed = true;
+        let mut process_holds = true;
+        let mut scanned: Option<Instant> = None;
+        let mut reported = None;
+        while !stop.load(Ordering::Relaxed) {
+            if let Some(active) = &active
+                && focus_changed
+            {
+                focus_holds = rules
+                    .focused
+                    .as_ref()
+                    .is_none_or(|selector| active.matches(selector))
+                    && (!rules.fullscreen || active.is_fullscreen());
+            }
+            if let Some(pattern) = &rules.process
+                && scanned.is_none_or(|at| at.elapsed() >= PROCESS_SCAN_INTERVAL)
+            {
+                scanned = Some(Instant::now());
+                process_holds = process_running(pattern);
+            }
+
+            let shown = focus_holds && process_holds;
+            if reported != Some(shown) {
+                reported = Some(shown);
+                if proxy.send_event(OverlayEvent::Visibility(shown)).is_err() {
+                    break;
+                }
+            }
+
+            focus_changed = match &mut active {
+                Some(active) => active.wait(POLL_INTERVAL)?,
+                None => {
+                    thread::sleep(POLL_INTERVAL);
+                    false
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Whether any process's name, or the file name it was started as,
+    /// matches `pattern`. `comm` alone is cut off at 15 bytes.
+    fn process_running(pattern: &Pattern) -> bool {
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return false;
+        };
+        entries.flatten().any(|entry| {
+            let name = entry.file_name();
+            if !name
+                .to_string_lossy()
+                .bytes()
+                .all(|byte| byte.is_ascii_digit())
+            {
+                return false;
+            }
+            let dir = entry.path();
+            let comm = fs::read_to_string(dir.join("comm")).unwrap_or_default();
+            if pattern.0.is_match(comm.trim_end()) {
+                return true;
+            }
+            let cmdline = fs::read(dir.join("cmdline")).unwrap_or_default();
+            let argv0 = cmdline.split(|byte| *byte == 0).next().unwrap_or_default();
+            let argv0 = String::from_utf8_lossy(argv0);
+            Path::new(argv0.as_ref())
+                .file_name()
+                .is_some_and(|file| pattern.0.is_match(&file.to_string_lossy()))
+        })
+    }
+}
@@ -0,0 +1,463 @@
+//! Overlay surfaces for wlroots-based compositors such as Sway and Hyprland,
+//! which neither let a regular window ignore input nor keep it on top.
+//!
+//! A wlr-layer-shell surface in the overlay layer does both. The surfaces
+//! live on a Wayland connection of their own next to winit's, and a
+//! background thread wakes the winit event loop whenever that connection
+//! has events.
+//!
+//! A headless compositor is enough to try it without a session:
+//!
+//! ```sh
+//! WLR_BACKENDS=headless WLR_RENDERER=pixman sway -c /dev/null &
+//! WAYLAND_DISPLAY=wayland-1 rs_overlay
+//! WAYLAND_DISPLAY=wayland-1 grim overlay.png
+//! ```
+
+use std::{
+    io,
+    os::fd::{AsRawFd as _, BorrowedFd},
+    ptr::NonNull,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState, Region},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry,
+    output::{OutputHandler, OutputState},
+    reexports::client::{
+        Connection, EventQueue, Proxy as _, QueueHandle,
+        backend::WaylandError,
+        globals::registry_queue_init,
+        protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    shell::{
+        WaylandSurface as _,
+        wlr_layer::{
+            Anchor, KeyboardInteractivity, Layer, LayerShell as WlrLayerShell, LayerShellHandler,
+            LayerSurface, LayerSurfaceConfigure,
+        },
+    },
+};
+use winit::{
+    dpi::PhysicalSize,
+    event_loop::{EventLoopProxy, EventLoopWindowTarget},
+    raw_window_handle::{
+        DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+        RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, WindowHandle,
+    },
+};
+
+use crate::{
+    event::OverlayEvent,
+    monitors::{Monitor, Monitors},
+};
+
+/// What compositors see as the layer surfaces' namespace, for their rules.
+const NAMESPACE: &str = "rs_overlay";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The overlay's own Wayland connection with the globals it needs for layer
+/// surfaces. Dropping it stops the background thread.
+pub(crate) struct LayerShell {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    stop: Arc<AtomicBool>,
+}
+
+impl LayerShell {
+    /// `None` unless winit runs on Wayland. Fails when the compositor has no
+    /// wlr-layer-shell, as GNOME does not.
+    pub(crate) fn connect(
+        target: &EventLoopWindowTarget<OverlayEvent>,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Option<Self>> {
+        let on_wayland = target
+            .display_handle()
+            .is_ok_and(|handle| matches!(handle.as_raw(), RawDisplayHandle::Wayland(_)));
+        if !on_wayland {
+            return Ok(None);
+        }
+
+        let conn = Connection::connect_to_env().context("connect to the Wayland compositor")?;
+        let (globals, mut queue) = registry_queue_init(&conn).context("list Wayland globals")?;
+        let qh = queue.handle();
+        let compositor = CompositorState::bind(&globals, &qh).context("bind wl_compositor")?;
+        let layer_shell = WlrLayerShell::bind(&globals, &qh)
+            .context("the compositor does not support wlr-layer-shell")?;
+        let mut state = State {
+            registry: RegistryState::new(&globals),
+            outputs: OutputState::new(&globals, &qh),
+            compositor,
+            layer_shell,
+            events: Vec::new(),
+        };
+        // Outputs describe themselves in events following their globals.
+        queue.roundtrip(&mut state).context("query outputs")?;
+        state.events.clear();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        spawn_reader(conn.clone(), proxy, stop.clone())?;
+        Ok(Some(Self {
+            conn,
+            queue,
+            state,
+            stop,
+        }))
+    }
+
+    pub(crate) fn monitors(&self) -> Monitors {
+        let available = self
+            .state
+            .outputs
+            .outputs()
+            .map(|output| {
+                let name = self.state.outputs.info(&output).and_then(|info| info.name);
+                Monitor::Output(Output { output, name })
+            })
+            .collect();
+        // Wayland has no notion of a primary output; the first one stands in.
+        Monitors::new(available, None)
+    }
+
+    /// Creates a click-through surface covering `output`, or the output the
+    /// compositor picks, and waits for its first configure.
+    pub(crate) fn open(&mut self, output: Option<&Output>) -> anyhow::Result<LayerWindow> {
+        let qh = self.queue.handle();
+        let surface = self.state.compositor.create_surface(&qh);
+        let layer = self.state.layer_shell.create_layer_surface(
+            &qh,
+            surface,
+            Layer::Overlay,
+            Some(NAMESPACE),
+            output.map(|output| &output.output),
+        );
+        layer.set_anchor(Anchor::all());
+        layer.set_exclusive_zone(0);
+        layer.set_keyboard_interactivity(KeyboardInteractivity::None);
+        // An empty input region passes every pointer event through.
+        let region = Region::new(&self.state.compositor).context("create input region")?;
+        layer
+            .wl_surface()
+            .set_input_region(Some(region.wl_region()));
+        layer.commit();
+
+        // Compositors answer the initial commit with a configure, and
+        // nothing may be drawn before it.
+        self.queue
+            .roundtrip(&mut self.state)
+            .context("configure layer surface")?;
+        let mut window = LayerWindow {
+            conn: self.conn.clone(),
+            layer,
+            size: (0, 0),
+            scale: 1,
+            closed: false,
+            redraw: true,
+        };
+        for event in &self.state.events {
+            window.apply(event);
+        }
+        if window.closed || window.size == (0, 0) {
+            anyhow::bail!("the compositor did not configure the layer surface");
+        }
+        Ok(window)
+    }
+
+    /// Handles what the background thread has read and returns the events
+    /// for the overlay's surfaces.
+    pub(crate) fn dispatch(&mut self) -> anyhow::Result<Vec<LayerEvent>> {
+        self.queue
+            .dispatch_pending(&mut self.state)
+            .context("dispatch Wayland events")?;
+        Ok(std::mem::take(&mut self.state.events))
+    }
+
+    /// Sends requests made since the last flush, such as buffer scales.
+    pub(crate) fn flush(&self) {
+        let _ = self.conn.flush();
+    }
+}
+
+impl Drop for LayerShell {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A Wayland output of the layer-shell connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Output {
+    output: WlOutput,
+    name: Option<String>,
+}
+
+impl Output {
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Outputs are the same while their global is, whatever they are called.
+impl PartialEq for Output {
+    fn eq(&self, other: &Self) -> bool {
+        self.output == other.output
+    }
+}
+
+/// What happened to the overlay's surfaces or to the outputs.
+#[derive(Debug)]
+pub(crate) enum LayerEvent {
+    /// The compositor sized a surface, in logical pixels.
+    Configured(WlSurface, (u32, u32)),
+    /// A surface moved onto outputs with a different integer scale.
+    Scale(WlSurface, i32),
+    /// The surface's output went away; it will never be shown again.
+    Closed(WlSurface),
+    OutputsChanged,
+}
+
+/// A layer surface sized by the compositor, which draws no input.
+pub(crate) struct LayerWindow {
+    conn: Connection,
+    layer: LayerSurface,
+    /// Logical size from the last configure.
+    size: (u32, u32),
+    scale: i32,
+    closed: bool,
+    redraw: bool,
+}
+
+impl LayerWindow {
+    /// Applies `event` if it is about this surface and returns whether it
+    /// was.
+    pub(crate) fn apply(&mut self, event: &LayerEvent) -> bool {
+        match event {
+            LayerEvent::Configured(surface, size) if surface == self.layer.wl_surface() => {
+                self.size = *size;
+            }
+            LayerEvent::Scale(surface, scale) if surface == self.layer.wl_surface() => {
+                self.scale = *scale;
+                self.layer.wl_surface().set_buffer_scale(*scale);
+            }
+            LayerEvent::Closed(surface) if surface == self.layer.wl_surface() => {
+                self.closed = true;
+            }
+            _ => return false,
+        }
+        self.redraw = true;
+        true
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub(crate) fn physical_size(&self) -> PhysicalSize<u32> {
+        let scale = self.scale.max(1) as u32;
+        PhysicalSize::new(self.size.0 * scale, self.size.1 * scale)
+    }
+
+    pub(crate) fn pixels_per_point(&self) -> f32 {
+        self.scale.max(1) as f32
+    }
+
+    pub(crate) fn size_in_points(&self) -> egui::Vec2 {
+        egui::vec2(self.size.0 as f32, self.size.1 as f32)
+    }
+
+    pub(crate) fn request_redraw(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Whether the surface should be drawn now, clearing the request.
+    pub(crate) fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.redraw) && !self.closed
+    }
+
+    pub(crate) fn handle(&self) -> SurfaceHandle {
+        SurfaceHandle {
+            conn: self.conn.clone(),
+            layer: self.layer.clone(),
+            size: self.physical_size(),
+        }
+    }
+}
+
+/// What wgpu draws to: the raw surface and display, kept alive for as long
+/// as the renderer holds on to them.
+#[derive(Clone)]
+pub(crate) struct SurfaceHandle {
+    conn: Connection,
+    layer: LayerSurface,
+    size: PhysicalSize<u32>,
+}
+
+impl SurfaceHandle {
+    pub(crate) fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+}
+
+impl HasWindowHandle for SurfaceHandle {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        let surface = NonNull::new(self.layer.wl_surface().id().as_ptr().cast())
+            .ok_or(HandleError::Unavailable)?;
+        let raw = RawWindowHandle::Wayland(WaylandWindowHandle::new(surface));
+        // The surface lives as long as `self.layer`.
+        Ok(unsafe { WindowHandle::borrow_raw(raw) })
+    }
+}
+
+impl HasDisplayHandle for SurfaceHandle {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        let display = NonNull::new(self.conn.backend().display_ptr().cast())
+            .ok_or(HandleError::Unavailable)?;
+        let raw = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(display));
+        // The display lives as long as `self.conn`.
+        Ok(unsafe { DisplayHandle::borrow_raw(raw) })
+    }
+}
+
+struct State {
+    registry: RegistryState,
+    outputs: OutputState,
+    compositor: CompositorState,
+    layer_shell: WlrLayerShell,
+    events: Vec<LayerEvent>,
+}
+
+impl CompositorHandler for State {
+    fn scale_factor_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        surface: &WlSurface,
+        new_factor: i32,
+    ) {
+        self.events
+            .push(LayerEvent::Scale(surface.clone(), new_factor));
+    }
+
+    fn transform_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &WlSurface,
+        _: smithay_client_toolkit::reexports::client::protocol::wl_output::Transform,
+    ) {
+    }
+
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlSurface, _: u32) {}
+}
+
+impl OutputHandler for State {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.outputs
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {
+        self.events.push(LayerEvent::OutputsChanged);
+    }
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {
+        self.events.push(LayerEvent::OutputsChanged);
+    }
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {
+        self.events.push(LayerEvent::OutputsChanged);
+    }
+}
+
+impl LayerShellHandler for State {
+    fn closed(&mut self, _: &Connection, _: &QueueHandle<Self>, layer: &LayerSurface) {
+        self.events
+            .push(LayerEvent::Closed(layer.wl_surface().clone()));
+    }
+
+    fn configure(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _: u32,
+    ) {
+        self.events.push(LayerEvent::Configured(
+            layer.wl_surface().clone(),
+            configure.new_size,
+        ));
+    }
+}
+
+impl ProvidesRegistryState for State {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+
+    registry_handlers![OutputState];
+}
+
+delegate_compositor!(State);
+delegate_output!(State);
+delegate_layer!(State);
+delegate_registry!(State);
+
+/// Reads the connection in the background and wakes the event loop with
+/// `OverlayEvent::Wayland` to dispatch what arrived, until `stop` is set.
+fn spawn_reader(
+    conn: Connection,
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("rs_overlay-wayland".to_owned())
+        .spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let read = match conn.prepare_read() {
+                    Some(guard) if wait_readable(guard.connection_fd(), POLL_INTERVAL) => {
+                        guard.read()
+                    }
+                    Some(_) => continue,
+                    // Events are already waiting to be dispatched.
+                    None => conn.backend().dispatch_inner_queue(),
+                };
+                let woken = match read {
+                    Ok(0) => continue,
+                    Ok(_) => proxy.send_event(OverlayEvent::Wayland).is_ok(),
+                    Err(WaylandError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                        continue;
+                    }
+                    Err(err) => {
+                        let _ = proxy.send_event(OverlayEvent::Status(format!(
+                            "lost the Wayland connection: {err}"
+                        )));
+                        false
+                    }
+                };
+                if !woken {
+                    break;
+                }
+            }
+        })
+        .context("spawn Wayland reader")
+}
+
+fn wait_readable(fd: BorrowedFd<'_>, timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    unsafe { libc::poll(&mut fd, 1, timeout_ms) > 0 }
+}
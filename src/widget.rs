@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    FpsTracker, Metrics,
+    config::{Anchor, MonitorSelector, ThemeConfig, WidgetConfig},
+};
+
+/// Per-frame data handed to every widget before it is painted.
+pub struct WidgetContext<'a> {
+    pub fps: &'a FpsTracker,
+    /// Samples pushed over the control socket.
+    pub metrics: &'a Metrics,
+    pub now: Instant,
+}
+
+/// Refresh interval of widgets that do not pick their own.
+pub const DEFAULT_REFRESH: Duration = Duration::from_millis(500);
+
+/// A piece of overlay content. `update` runs before `paint` whenever the
+/// widget's refresh interval has elapsed; `paint` draws into the `Ui` of the
+/// widget's own area every frame.
+pub trait Widget {
+    fn update(&mut self, ctx: &WidgetContext<'_>);
+    fn paint(&mut self, ui: &mut egui::Ui);
+
+    /// How often the widget wants `update`, unless its config sets
+    /// `refresh_ms`. The overlay sleeps until the first widget is due, so
+    /// this is also how often it wakes up.
+    fn refresh_interval(&self) -> Duration {
+        DEFAULT_REFRESH
+    }
+
+    /// Replaces the text the widget shows, as asked over the control socket.
+    /// Returns `false` for widgets that show no text of their own.
+    fn set_text(&mut self, text: &str) -> bool {
+        let _ = text;
+        false
+    }
+}
+
+/// Text appearance resolved from the theme and per-widget overrides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetStyle {
+    pub font: egui::FontId,
+    pub color: egui::Color32,
+}
+
+impl WidgetStyle {
+    pub fn resolve(theme: &ThemeConfig, widget: &WidgetConfig) -> Self {
+        Self {
+            font: egui::FontId::new(
+                widget.font_size.unwrap_or(theme.font_size),
+                widget.font.unwrap_or(theme.font).into(),
+            ),
+            color: widget.color.unwrap_or(theme.color).0,
+        }
+    }
+
+    pub fn text(&self, text: impl Into<String>) -> egui::RichText {
+        egui::RichText::new(text)
+            .font(self.font.clone())
+            .color(self.color)
+    }
+}
+
+impl Default for WidgetStyle {
+    fn default() -> Self {
+        Self::resolve(&ThemeConfig::default(), &WidgetConfig::default())
+    }
+}
+
+/// Where a widget sits on its monitor and how often it is updated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub anchor: Anchor,
+    pub offset: [f32; 2],
+    /// Factor the widget is drawn at, around its anchored corner or edge.
+    pub scale: f32,
+    pub refresh: Option<Duration>,
+    /// `None` shows the widget on the first overlay window.
+    pub monitor: Option<MonitorSelector>,
+    /// Layouts the widget is part of; empty for all of them.
+    pub layouts: Vec<String>,
+}
+
+impl Placement {
+    pub fn from_config(widget: &WidgetConfig) -> Self {
+        Self {
+            anchor: widget.anchor,
+            offset: widget.offset,
+            scale: widget.scale,
+            refresh: widget.refresh_interval(),
+            monitor: widget.monitor.clone(),
+            layouts: widget.layouts.clone(),
+        }
+    }
+
+    /// Whether the widget shows in `layout`, or with no layouts named at all.
+    pub fn in_layout(&self, layout: Option<&str>) -> bool {
+        self.layouts.is_empty()
+            || layout.is_some_and(|layout| self.layouts.iter().any(|name| name == layout))
+    }
+}
+
+impl From<egui::Pos2> for Placement {
+    fn from(position: egui::Pos2) -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            offset: [position.x, position.y],
+            scale: 1.0,
+            refresh: None,
+            monitor: None,
+            layouts: Vec::new(),
+        }
+    }
+}
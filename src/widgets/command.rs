@@ -0,0 +1,269 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use super::process::{Producer, ProducerEvent};
+use crate::{
+    Widget, WidgetContext,
+    config::{ClassStyle, CommandOptions},
+    widget::{DEFAULT_REFRESH, WidgetStyle},
+};
+
+/// Text, tooltip and optional percentage bar of a script written for
+/// Waybar's custom module, which runs as is.
+pub struct CommandWidget {
+    options: CommandOptions,
+    style: WidgetStyle,
+    classes: HashMap<String, ClassStyle>,
+    producer: Option<Producer>,
+    /// Whether the running command closed its output.
+    closed: bool,
+    /// When the current or last run started.
+    started: Option<Instant>,
+    output: Output,
+    /// Why nothing current can be shown.
+    error: Option<String>,
+}
+
+/// One line of output, in Waybar's JSON form.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Output {
+    text: String,
+    tooltip: Option<String>,
+    class: Classes,
+    percentage: Option<f32>,
+}
+
+/// Waybar takes either one class or a list of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Classes {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for Classes {
+    fn default() -> Self {
+        Self::Many(Vec::new())
+    }
+}
+
+impl Classes {
+    fn as_slice(&self) -> &[String] {
+        match self {
+            Self::One(class) => std::slice::from_ref(class),
+            Self::Many(classes) => classes,
+        }
+    }
+}
+
+impl Output {
+    fn parse(line: &str) -> Result<Self, serde_json::Error> {
+        if line.trim_start().starts_with('{') {
+            serde_json::from_str(line)
+        } else {
+            Ok(Self {
+                text: line.to_owned(),
+                ..Self::default()
+            })
+        }
+    }
+}
+
+impl CommandWidget {
+    pub fn new(options: CommandOptions) -> Self {
+        Self {
+            options,
+            style: WidgetStyle::default(),
+            classes: HashMap::new(),
+            producer: None,
+            closed: false,
+            started: None,
+            output: Output::default(),
+            error: None,
+        }
+    }
+
+    pub fn with_style(mut self, style: WidgetStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Styles for the classes the command reports, usually the theme's.
+    pub fn with_classes(mut self, classes: HashMap<String, ClassStyle>) -> Self {
+        self.classes = classes;
+        self
+    }
+
+    /// The style of the last listed class that has one.
+    fn class_style(&self) -> ClassStyle {
+        self.output
+            .class
+            .as_slice()
+            .iter()
+            .rev()
+            .find_map(|class| self.classes.get(class))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Widget for CommandWidget {
+    fn update(&mut self, ctx: &WidgetContext<'_>) {
+        let due = match (self.started, self.options.interval()) {
+            (None, _) => true,
+            // A run that is still going is not started again.
+            (Some(started), Some(interval)) => {
+                self.producer.is_none() && ctx.now.saturating_duration_since(started) >= interval
+            }
+            (Some(_), None) => false,
+        };
+        if due {
+            self.started = Some(ctx.now);
+            match Producer::spawn(&self.options.command, false) {
+                Ok(producer) => self.producer = Some(producer),
+                Err(err) => self.error = Some(format!("`{}`: {err}", self.options.command)),
+            }
+        }
+
+        let Some(producer) = &mut self.producer else {
+            return;
+        };
+        for event in producer.events() {
+            match event {
+                ProducerEvent::Line(line) => match Output::parse(&line) {
+                    Ok(output) => {
+                        self.output = output;
+                        self.error = None;
+                    }
+                    Err(err) => {
+                        self.error =
+                            Some(format!("`{}`: invalid output: {err}", self.options.command));
+                    }
+                },
+                ProducerEvent::Closed => self.closed = true,
+            }
+        }
+        // A command may close its output and keep running, so it is only
+        // let go once it exited, checked again on later updates.
+        if self.closed
+            && let Some(status) = producer.exit_status()
+        {
+            // Scripts that print once and exit are fine; failing ones are not.
+            if !status.success() {
+                self.error = Some(format!("`{}` exited ({status})", self.options.command));
+            }
+            self.producer = None;
+            self.closed = false;
+        }
+    }
+
+    fn paint(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.error {
+            ui.label(self.style.text(error.as_str()));
+            return;
+        }
+        // Waybar hides modules without text.
+        if self.output.text.is_empty() {
+            return;
+        }
+        let class = self.class_style();
+        let color = class.color.map_or(self.style.color, |color| color.0);
+        let mut text = self.style.text(self.output.text.as_str()).color(color);
+        if let Some(background) = class.background {
+            text = text.background_color(background.0);
+        }
+        let mut rect = ui.label(text).rect;
+
+        if self.options.bar
+            && let Some(percentage) = self.output.percentage
+        {
+            let (bar, _) =
+                ui.allocate_exact_size(self.options.bar_size.into(), egui::Sense::hover());
+            let filled = egui::Rect::from_min_size(
+                bar.min,
+                egui::vec2(
+                    bar.width() * (percentage / 100.0).clamp(0.0, 1.0),
+                    bar.height(),
+                ),
+            );
+            let painter = ui.painter();
+            painter.rect_filled(bar, 0.0, self.options.bar_background.0);
+            painter.rect_filled(filled, 0.0, color);
+            rect = rect.union(bar);
+        }
+
+        // The pointer only reaches the widget in edit mode, and the edit
+        // session's drag on top keeps it from being hovered.
+        let id = ui.id().with("command_tooltip");
+        if let Some(tooltip) = self.output.tooltip.as_deref().filter(|tip| !tip.is_empty())
+            && ui
+                .interact(rect, id, egui::Sense::hover())
+                .contains_pointer()
+        {
+            egui::show_tooltip_text(ui.ctx(), id, tooltip);
+        }
+    }
+
+    fn refresh_interval(&self) -> Duration {
+        self.options
+            .interval()
+            .map_or(DEFAULT_REFRESH, |interval| interval.min(DEFAULT_REFRESH))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{FpsTracker, Metrics};
+
+    fn widget(command: &str) -> CommandWidget {
+        let options = toml::from_str(&format!("command = {command:?}")).expect("valid options");
+        CommandWidget::new(options)
+    }
+
+    /// Updates `widget` until `done` holds, failing after a few seconds.
+    fn update_until(widget: &mut CommandWidget, done: impl Fn(&CommandWidget) -> bool) {
+        let (fps, metrics) = (FpsTracker::new(), Metrics::new());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let now = Instant::now();
+            widget.update(&WidgetContext {
+                fps: &fps,
+                metrics: &metrics,
+                now,
+            });
+            // Never waits for the command.
+            assert!(now.elapsed() < Duration::from_millis(100));
+            if done(widget) {
+                return;
+            }
+            assert!(now < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn commands_that_close_their_output_keep_running() {
+        let mut widget = widget("echo 42; exec >&-; sleep 60");
+        update_until(&mut widget, |widget| widget.closed);
+        assert_eq!(widget.output.text, "42");
+        assert!(widget.producer.is_some());
+        assert!(widget.error.is_none());
+    }
+
+    #[test]
+    fn failing_commands_are_reported_once_they_exit() {
+        let mut widget = widget("echo 42; exit 3");
+        update_until(&mut widget, |widget| widget.producer.is_none());
+        assert_eq!(widget.output.text, "42");
+        let error = widget.error.as_deref().unwrap_or_default();
+        assert!(error.contains("exited"), "{error}");
+    }
+}
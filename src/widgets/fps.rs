@@ -0,0 +1,78 @@
+use crate::{
+    Widget, WidgetContext,
+    config::{FpsMetric, FpsOptions},
+    fps::FrameStats,
+    widget::WidgetStyle,
+};
+
+pub struct FpsWidget {
+    options: FpsOptions,
+    lines: Vec<String>,
+    style: WidgetStyle,
+}
+
+impl FpsWidget {
+    pub fn new() -> Self {
+        Self {
+            options: FpsOptions::default(),
+            lines: Vec::new(),
+            style: WidgetStyle::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: WidgetStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_options(mut self, options: FpsOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl Default for FpsWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget for FpsWidget {
+    fn update(&mut self, ctx: &WidgetContext<'_>) {
+        let stats = ctx.fps.stats(self.options.window());
+        self.lines = self
+            .options
+            .metrics
+            .iter()
+            .map(|metric| format_metric(*metric, ctx.fps.fps(), &stats))
+            .collect();
+    }
+
+    fn paint(&mut self, ui: &mut egui::Ui) {
+        egui::Frame::none().show(ui, |ui| {
+            for line in &self.lines {
+                ui.label(self.style.text(line.as_str()));
+            }
+        });
+    }
+}
+
+fn format_metric(metric: FpsMetric, fps: f32, stats: &FrameStats) -> String {
+    let (label, value) = match metric {
+        FpsMetric::Fps => ("FPS", format!("{fps:.1}")),
+        FpsMetric::Min => ("min", format!("{:.2} ms", stats.min_ms)),
+        FpsMetric::Max => ("max", format!("{:.2} ms", stats.max_ms)),
+        FpsMetric::Mean => ("mean", format!("{:.2} ms", stats.mean_ms)),
+        FpsMetric::StdDev => ("σ", format!("{:.2} ms", stats.std_dev_ms)),
+        FpsMetric::P50 => ("p50", format!("{:.2} ms", stats.p50_ms)),
+        FpsMetric::P95 => ("p95", format!("{:.2} ms", stats.p95_ms)),
+        FpsMetric::P99 => ("p99", format!("{:.2} ms", stats.p99_ms)),
+        FpsMetric::Low1 => ("1% low", format!("{:.1}", stats.low_1_fps)),
+        FpsMetric::Low01 => ("0.1% low", format!("{:.1}", stats.low_0_1_fps)),
+    };
+    // No source configured, or it stopped sending frames.
+    if stats.frames == 0 {
+        return format!("{label}: –");
+    }
+    format!("{label}: {value}")
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use crate::{
+    Widget, WidgetContext,
+    config::{GraphOptions, GraphScale},
+    widget::WidgetStyle,
+};
+
+/// Headroom above the slowest visible frame, or the lowest reference line
+/// when every frame is faster, when the y axis scales itself.
+const AUTO_SCALE_HEADROOM: f32 = 1.2;
+
+/// Scrolling faster than this looks no smoother but costs the game CPU time.
+const MIN_REFRESH: Duration = Duration::from_millis(33);
+
+/// Scrolling plot of recent frame times, newest on the right.
+pub struct GraphWidget {
+    options: GraphOptions,
+    style: WidgetStyle,
+    /// (seconds before the newest frame, frame time in ms), or (seconds ago,
+    /// value) for a metric, oldest first.
+    samples: Vec<(f32, f32)>,
+}
+
+impl GraphWidget {
+    pub fn new() -> Self {
+        Self {
+            options: GraphOptions::default(),
+            style: WidgetStyle::default(),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn with_style(mut self, style: WidgetStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_options(mut self, options: GraphOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn y_max(&self) -> f32 {
+        match self.options.scale {
+            GraphScale::Fixed(ms) => ms,
+            GraphScale::Auto => {
+                let slowest = self.samples.iter().map(|(_, ms)| *ms).fold(0.0, f32::max);
+                let lowest_reference = self
+                    .options
+                    .reference_ms
+                    .iter()
+                    .copied()
+                    .reduce(f32::min)
+                    .unwrap_or(0.0);
+                (slowest.max(lowest_reference) * AUTO_SCALE_HEADROOM).max(1.0)
+            }
+        }
+    }
+}
+
+impl Default for GraphWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget for GraphWidget {
+    fn update(&mut self, ctx: &WidgetContext<'_>) {
+        let span = self.options.span();
+        self.samples.clear();
+        match &self.options.metric {
+            // Ages from now, so the plot keeps scrolling between pushes.
+            Some(metric) => {
+                for (at, value) in ctx.metrics.samples(metric).rev() {
+                    let age = ctx.now.saturating_duration_since(at);
+                    if age > span {
+                        break;
+                    }
+                    self.samples.push((age.as_secs_f32(), value));
+                }
+            }
+            None => {
+                let mut frames = ctx.fps.frame_times().rev();
+                let Some((newest, duration)) = frames.next() else {
+                    return;
+                };
+                self.samples.push((0.0, duration.as_secs_f32() * 1000.0));
+                for (end, duration) in frames {
+                    let age = newest.saturating_duration_since(end);
+                    if age > span {
+                        break;
+                    }
+                    self.samples
+                        .push((age.as_secs_f32(), duration.as_secs_f32() * 1000.0));
+                }
+            }
+        }
+        self.samples.reverse();
+    }
+
+    /// Often enough to scroll by about a pixel per update.
+    fn refresh_interval(&self) -> Duration {
+        self.options
+            .span()
+            .div_f32(self.options.width.max(1.0))
+            .max(MIN_REFRESH)
+    }
+
+    fn paint(&mut self, ui: &mut egui::Ui) {
+        let size = egui::vec2(self.options.width, self.options.height);
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+
+        if let Some(background) = self.options.background {
+            painter.rect_filled(rect, 2.0, background.0);
+        }
+
+        let span = self.options.span().as_secs_f32().max(f32::EPSILON);
+        let y_max = self.y_max();
+        let x_at = |age: f32| rect.right() - (age / span) * rect.width();
+        let y_at = |ms: f32| rect.bottom() - (ms / y_max).min(1.0) * rect.height();
+
+        if let Some(spike_ms) = self.options.spike_ms {
+            for window in self.samples.windows(2) {
+                let [(previous_age, _), (age, ms)] = [window[0], window[1]];
+                if ms > spike_ms {
+                    let band = egui::Rect::from_x_y_ranges(
+                        x_at(previous_age)..=x_at(age).max(x_at(previous_age) + 1.0),
+                        rect.y_range(),
+                    );
+                    painter.rect_filled(band, 0.0, self.options.spike_color.0);
+                }
+            }
+        }
+
+        let reference_stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(60));
+        let label_font = egui::FontId::monospace(10.0);
+        for &reference in &self.options.reference_ms {
+            if reference >= y_max {
+                continue;
+            }
+            let y = y_at(reference);
+            painter.hline(rect.x_range(), y, reference_stroke);
+            painter.text(
+                egui::pos2(rect.left() + 2.0, y - 1.0),
+                egui::Align2::LEFT_BOTTOM,
+                match self.options.metric {
+                    Some(_) => format!("{reference:.1}"),
+                    None => format!("{reference:.1} ms"),
+                },
+                label_font.clone(),
+                reference_stroke.color,
+            );
+        }
+
+        let points: Vec<egui::Pos2> = self
+            .samples
+            .iter()
+            .map(|&(age, ms)| egui::pos2(x_at(age), y_at(ms)))
+            .collect();
+        if points.len() >= 2 {
+            painter.add(egui::Shape::line(
+                points,
+                egui::Stroke::new(1.5, self.style.color),
+            ));
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use super::process::{Producer, ProducerEvent};
+use crate::{
+    Widget, WidgetContext,
+    config::{Color, I3barOptions},
+    widget::WidgetStyle,
+};
+
+/// i3bar's numbers for the buttons it reports; scrolling is 4 and 5.
+const BUTTONS: [(egui::PointerButton, u8); 5] = [
+    (egui::PointerButton::Primary, 1),
+    (egui::PointerButton::Middle, 2),
+    (egui::PointerButton::Secondary, 3),
+    (egui::PointerButton::Extra1, 8),
+    (egui::PointerButton::Extra2, 9),
+];
+
+/// Blocks of an i3bar protocol producer, side by side. The command is
+/// started on the first update and stopped with the widget. In edit mode,
+/// clicks on a block are written back to it when its header asks for them.
+pub struct I3barWidget {
+    options: I3barOptions,
+    style: WidgetStyle,
+    producer: Option<Producer>,
+    started: bool,
+    /// Whether the running command closed its output.
+    closed: bool,
+    stage: Stage,
+    /// Whether a click was written, so later ones need a comma.
+    clicked: bool,
+    blocks: Vec<Block>,
+    /// Why the command no longer updates the blocks.
+    error: Option<String>,
+}
+
+enum Stage {
+    /// Lines read so far of a header that is not complete yet.
+    Header(String),
+    /// The producer writes no header, and each line is the whole status.
+    Plain,
+    /// Input after the header that does not form a whole status yet.
+    Stream { opened: bool, pending: String },
+}
+
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    click_events: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct Block {
+    full_text: String,
+    color: Option<String>,
+    background: Option<String>,
+    border: Option<String>,
+    name: Option<String>,
+    instance: Option<String>,
+    urgent: bool,
+    separator: bool,
+    separator_block_width: u32,
+}
+
+impl Default for Block {
+    fn default() -> Self {
+        Self {
+            full_text: String::new(),
+            color: None,
+            background: None,
+            border: None,
+            name: None,
+            instance: None,
+            urgent: false,
+            separator: true,
+            separator_block_width: 9,
+        }
+    }
+}
+
+/// A click to hand back to the producer.
+struct Click {
+    block: usize,
+    button: u8,
+    modifiers: Vec<&'static str>,
+    position: egui::Pos2,
+    relative: egui::Vec2,
+    size: egui::Vec2,
+}
+
+impl I3barWidget {
+    pub fn new(options: I3barOptions) -> Self {
+        Self {
+            options,
+            style: WidgetStyle::default(),
+            producer: None,
+            started: false,
+            closed: false,
+            stage: Stage::Header(String::new()),
+            clicked: false,
+            blocks: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn with_style(mut self, style: WidgetStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn read(&mut self, line: String) -> Result<(), String> {
+        match &mut self.stage {
+            Stage::Header(text) => {
+                if text.is_empty() && !line.trim_start().starts_with('{') {
+                    self.stage = Stage::Plain;
+                    return self.read(line);
+                }
+                text.push_str(&line);
+                text.push('\n');
+                let header = match serde_json::from_str::<Header>(text) {
+                    Ok(header) => header,
+                    Err(err) if err.is_eof() => return Ok(()),
+                    Err(err) => return Err(format!("invalid header: {err}")),
+                };
+                if header.click_events
+                    && let Some(producer) = &self.producer
+                {
+                    // Clicks form an infinite array too.
+                    producer.send("[\n".to_owned());
+                }
+                self.stage = Stage::Stream {
+                    opened: false,
+                    pending: String::new(),
+                };
+            }
+            Stage::Plain => {
+                self.blocks = vec![Block {
+                    full_text: line,
+                    ..Block::default()
+                }];
+            }
+            Stage::Stream { opened, pending } => {
+                pending.push_str(&line);
+                pending.push('\n');
+                if !*opened {
+                    let rest = pending.trim_start();
+                    if rest.is_empty() {
+                        return Ok(());
+                    }
+                    let Some(rest) = rest.strip_prefix('[') else {
+                        return Err("expected `[` after the header".to_owned());
+                    };
+                    *pending = rest.to_owned();
+                    *opened = true;
+                }
+                if let Some(blocks) = next_statuses(pending)? {
+                    self.blocks = blocks;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send_click(&mut self, click: Click) {
+        let (Some(producer), Some(block)) = (&self.producer, self.blocks.get(click.block)) else {
+            return;
+        };
+        let event = json!({
+            "name": block.name,
+            "instance": block.instance,
+            "button": click.button,
+            "modifiers": click.modifiers,
+            "x": click.position.x.round() as i32,
+            "y": click.position.y.round() as i32,
+            "relative_x": click.relative.x.round() as i32,
+            "relative_y": click.relative.y.round() as i32,
+            "width": click.size.x.round() as i32,
+            "height": click.size.y.round() as i32,
+        });
+        let separator = if self.clicked { "," } else { "" };
+        producer.send(format!("{separator}{event}\n"));
+        self.clicked = true;
+    }
+}
+
+/// Takes every complete status array off the front of `pending` and returns
+/// the newest, leaving an incomplete one for the next line.
+fn next_statuses(pending: &mut String) -> Result<Option<Vec<Block>>, String> {
+    let mut latest = None;
+    loop {
+        // Commas between the statuses, and the end of the array should the
+        // producer ever close it.
+        let rest = pending.trim_start_matches(|c: char| c == ',' || c == ']' || c.is_whitespace());
+        let skipped = pending.len() - rest.len();
+        let mut statuses = serde_json::Deserializer::from_str(rest).into_iter::<Vec<Block>>();
+        let consumed = match statuses.next() {
+            Some(Ok(blocks)) => {
+                latest = Some(blocks);
+                statuses.byte_offset()
+            }
+            Some(Err(err)) if err.is_eof() => 0,
+            Some(Err(err)) => return Err(format!("invalid status: {err}")),
+            None => 0,
+        };
+        pending.drain(..skipped + consumed);
+        if consumed == 0 {
+            return Ok(latest);
+        }
+    }
+}
+
+fn parse_color(color: Option<&str>) -> Option<egui::Color32> {
+    color.and_then(Color::parse).map(|color| color.0)
+}
+
+/// The button clicked on `rect` this frame, and where on the screen. Pointer
+/// input only reaches the widgets in edit mode.
+fn click_on(
+    ui: &egui::Ui,
+    id: egui::Id,
+    rect: egui::Rect,
+) -> Option<(u8, Vec<&'static str>, egui::Pos2)> {
+    // Hover, since the edit session's drag on top would take a click.
+    if !ui
+        .interact(rect, id, egui::Sense::hover())
+        .contains_pointer()
+    {
+        return None;
+    }
+    ui.input(|input| {
+        let button = BUTTONS
+            .iter()
+            .find(|(button, _)| input.pointer.button_clicked(*button))
+            .map(|(_, number)| *number)
+            .or(match input.raw_scroll_delta.y {
+                delta if delta > 0.0 => Some(4),
+                delta if delta < 0.0 => Some(5),
+                _ => None,
+            })?;
+        let mut modifiers = Vec::new();
+        if input.modifiers.shift {
+            modifiers.push("Shift");
+        }
+        if input.modifiers.ctrl {
+            modifiers.push("Control");
+        }
+        if input.modifiers.alt {
+            modifiers.push("Mod1");
+        }
+        Some((button, modifiers, input.pointer.interact_pos()?))
+    })
+}
+
+impl Widget for I3barWidget {
+    fn update(&mut self, _ctx: &WidgetContext<'_>) {
+        if !self.started {
+            self.started = true;
+            match Producer::spawn(&self.options.command, true) {
+                Ok(producer) => self.producer = Some(producer),
+                Err(err) => self.error = Some(format!("`{}`: {err}", self.options.command)),
+            }
+        }
+        let Some(producer) = &self.producer else {
+            return;
+        };
+        let events: Vec<_> = producer.events().collect();
+        for event in events {
+            let result = match event {
+                ProducerEvent::Line(line) => self.read(line),
+                ProducerEvent::Closed => {
+                    self.closed = true;
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                self.error = Some(format!("`{}` {err}", self.options.command));
+                self.producer = None;
+                return;
+            }
+        }
+        // The command may keep running after closing its output, so its exit
+        // status is added once later updates find it.
+        if self.closed
+            && let Some(producer) = &mut self.producer
+        {
+            self.error = Some(match producer.exit_status() {
+                Some(status) => {
+                    self.producer = None;
+                    format!("`{}` exited ({status})", self.options.command)
+                }
+                None => format!("`{}` closed its output", self.options.command),
+            });
+        }
+    }
+
+    fn paint(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.error {
+            ui.label(self.style.text(error.as_str()));
+            return;
+        }
+        let mut click = None;
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            let visible: Vec<_> = (self.blocks.iter().enumerate())
+                .filter(|(_, block)| !block.full_text.is_empty())
+                .collect();
+            for (position, &(index, block)) in visible.iter().enumerate() {
+                let (color, background) = if block.urgent {
+                    (
+                        self.options.urgent_color.0,
+                        Some(self.options.urgent_background.0),
+                    )
+                } else {
+                    (
+                        parse_color(block.color.as_deref()).unwrap_or(self.style.color),
+                        parse_color(block.background.as_deref()),
+                    )
+                };
+                let mut text = self.style.text(block.full_text.as_str()).color(color);
+                if let Some(background) = background {
+                    text = text.background_color(background);
+                }
+                let rect = ui.label(text).rect;
+                if let Some(border) = parse_color(block.border.as_deref()) {
+                    ui.painter()
+                        .rect_stroke(rect, 0.0, egui::Stroke::new(1.0, border));
+                }
+                if let Some((button, modifiers, position)) =
+                    click_on(ui, ui.id().with(("i3bar_block", index)), rect)
+                {
+                    // The area's scale applies to what the producer sees,
+                    // which is in pixels.
+                    let transform = ui
+                        .ctx()
+                        .memory(|memory| memory.layer_transforms.get(&ui.layer_id()).copied())
+                        .unwrap_or_default();
+                    let rect = transform * rect;
+                    let pixels_per_point = ui.ctx().pixels_per_point();
+                    click = Some(Click {
+                        block: index,
+                        button,
+                        modifiers,
+                        position: position * pixels_per_point,
+                        relative: (position - rect.min) * pixels_per_point,
+                        size: rect.size() * pixels_per_point,
+                    });
+                }
+
+                if position + 1 < visible.len() {
+                    let half = block.separator_block_width as f32 / 2.0;
+                    ui.add_space(half);
+                    if block.separator {
+                        ui.painter().vline(
+                            ui.cursor().min.x,
+                            rect.y_range(),
+                            egui::Stroke::new(1.0, self.options.separator_color.0),
+                        );
+                    }
+                    ui.add_space(half);
+                }
+            }
+        });
+        if let Some(click) = click {
+            self.send_click(click);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A widget fed `lines` without a running command.
+    fn read(lines: &[&str]) -> Result<I3barWidget, String> {
+        let options = toml::from_str("command = \"true\"").expect("valid options");
+        let mut widget = I3barWidget::new(options);
+        for line in lines {
+            widget.read((*line).to_owned())?;
+        }
+        Ok(widget)
+    }
+
+    fn texts(widget: &I3barWidget) -> Vec<&str> {
+        widget
+            .blocks
+            .iter()
+            .map(|block| block.full_text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn header_may_span_lines() {
+        let widget = read(&[
+            "{\"version\": 1,",
+            "\"click_events\": true}",
+            "[",
+            "[{\"full_text\": \"cpu\"}]",
+        ])
+        .unwrap();
+        assert_eq!(texts(&widget), ["cpu"]);
+    }
+
+    #[test]
+    fn statuses_may_span_lines() {
+        let widget = read(&[
+            "{\"version\": 1}",
+            "[[{\"full_text\": \"cpu\",",
+            "\"color\": \"#ff0000\"},",
+            "{\"full_text\": \"mem\", \"urgent\": true}]",
+        ])
+        .unwrap();
+        assert_eq!(texts(&widget), ["cpu", "mem"]);
+        assert_eq!(widget.blocks[0].color.as_deref(), Some("#ff0000"));
+        assert!(widget.blocks[1].urgent);
+    }
+
+    #[test]
+    fn later_statuses_follow_a_comma() {
+        let widget = read(&[
+            "{\"version\": 1}",
+            "[",
+            "[{\"full_text\": \"1\"}]",
+            ",[{\"full_text\": \"2\"}]",
+            ",[{\"full_text\": \"3\"}],[{\"full_text\": \"4\"}]",
+        ])
+        .unwrap();
+        // The newest of several statuses on one line wins.
+        assert_eq!(texts(&widget), ["4"]);
+    }
+
+    #[test]
+    fn producers_without_a_header_write_plain_lines() {
+        let widget = read(&["12:00", "12:01"]).unwrap();
+        assert_eq!(texts(&widget), ["12:01"]);
+        assert!(widget.blocks[0].separator);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn closed_output_is_reported_without_waiting_for_the_exit() {
+        use std::{
+            thread,
+            time::{Duration, Instant},
+        };
+
+        use crate::{FpsTracker, Metrics};
+
+        let options = toml::from_str("command = \"exec >&-; sleep 60\"").expect("valid options");
+        let mut widget = I3barWidget::new(options);
+        let (fps, metrics) = (FpsTracker::new(), Metrics::new());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while widget.error.is_none() {
+            let now = Instant::now();
+            assert!(now < deadline, "timed out");
+            widget.update(&WidgetContext {
+                fps: &fps,
+                metrics: &metrics,
+                now,
+            });
+            assert!(now.elapsed() < Duration::from_millis(100));
+            thread::sleep(Duration::from_millis(10));
+        }
+        let error = widget.error.as_deref().unwrap_or_default();
+        assert!(error.ends_with("closed its output"), "{error}");
+        assert!(widget.producer.is_some());
+    }
+
+    #[test]
+    fn invalid_input_is_an_error() {
+        assert!(read(&["{\"version\": 1}", "{}"]).is_err());
+        assert!(read(&["{\"version\": 1}", "[", "[{\"full_text\": 1}]"]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+mod command;
+mod fps;
+mod graph;
+mod i3bar;
+mod process;
+mod text;
+
+pub use command::CommandWidget;
+pub use fps::FpsWidget;
+pub use graph::GraphWidget;
+pub use i3bar::I3barWidget;
+pub use text::TextWidget;
+
+use crate::{
+    Widget,
+    config::{ThemeConfig, WidgetConfig, WidgetKind},
+    widget::WidgetStyle,
+};
+
+/// Builds the built-in widget described by a `[[widget]]` table.
+pub fn from_config(theme: &ThemeConfig, config: &WidgetConfig) -> Box<dyn Widget> {
+    let style = WidgetStyle::resolve(theme, config);
+    match &config.kind {
+        WidgetKind::Fps(options) => Box::new(
+            FpsWidget::new()
+                .with_style(style)
+                .with_options(options.clone()),
+        ),
+        WidgetKind::Graph(options) => Box::new(
+            GraphWidget::new()
+                .with_style(style)
+                .with_options(options.clone()),
+        ),
+        WidgetKind::Text(options) => Box::new(
+            TextWidget::new()
+                .with_style(style)
+                .with_options(options.clone()),
+        ),
+        WidgetKind::I3bar(options) => Box::new(I3barWidget::new(options.clone()).with_style(style)),
+        WidgetKind::Command(options) => Box::new(
+            CommandWidget::new(options.clone())
+                .with_style(style)
+                .with_classes(theme.classes.clone()),
+        ),
+    }
+}
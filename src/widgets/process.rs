@@ -0,0 +1,131 @@
+use std::{
+    io::{self, BufRead as _, BufReader, Write as _},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+pub(crate) enum ProducerEvent {
+    Line(String),
+    /// The command closed its output, usually by exiting.
+    Closed,
+}
+
+/// A shell command whose output a widget shows. Its output is read on a
+/// background thread, so a slow command never holds up a frame. Dropping it
+/// kills the command.
+pub(crate) struct Producer {
+    child: Child,
+    events: Receiver<ProducerEvent>,
+    /// Set when the command reads input.
+    input: Option<Sender<String>>,
+}
+
+impl Producer {
+    /// Runs `command` with the platform's shell. With `input`, text handed to
+    /// `send` goes to its standard input.
+    pub(crate) fn spawn(command: &str, input: bool) -> io::Result<Self> {
+        let mut shell = shell(command);
+        shell
+            .stdin(if input { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped());
+        // Its own process group, so the commands the shell starts are
+        // killed along with it.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+        let mut child = shell.spawn()?;
+
+        let (sender, events) = mpsc::channel();
+        let stdout = child.stdout.take().expect("stdout is piped");
+        thread::Builder::new()
+            .name("rs_overlay-producer".to_owned())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if sender.send(ProducerEvent::Line(line)).is_err() {
+                        return;
+                    }
+                }
+                let _ = sender.send(ProducerEvent::Closed);
+            })?;
+
+        let input = match child.stdin.take() {
+            Some(stdin) => Some(spawn_writer(stdin)?),
+            None => None,
+        };
+        Ok(Self {
+            child,
+            events,
+            input,
+        })
+    }
+
+    /// What happened since the last call, oldest first.
+    pub(crate) fn events(&self) -> impl Iterator<Item = ProducerEvent> + '_ {
+        self.events.try_iter()
+    }
+
+    /// Writes `text` to the command's input, unless it was spawned without.
+    pub(crate) fn send(&self, text: String) {
+        if let Some(input) = &self.input {
+            let _ = input.send(text);
+        }
+    }
+
+    /// `None` while the command runs.
+    pub(crate) fn exit_status(&mut self) -> Option<ExitStatus> {
+        self.child.try_wait().ok().flatten()
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Ok(pid) = i32::try_from(self.child.id())
+            && self.exit_status().is_none()
+        {
+            unsafe {
+                libc::kill(-pid, libc::SIGTERM);
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Writes on a thread of its own, as a command that stops reading would
+/// otherwise block whoever writes once the pipe is full.
+fn spawn_writer(mut stdin: ChildStdin) -> io::Result<Sender<String>> {
+    let (sender, texts) = mpsc::channel::<String>();
+    thread::Builder::new()
+        .name("rs_overlay-producer-input".to_owned())
+        .spawn(move || {
+            for text in texts {
+                if stdin
+                    .write_all(text.as_bytes())
+                    .and_then(|()| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        })?;
+    Ok(sender)
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
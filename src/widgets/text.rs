@@ -0,0 +1,47 @@
+use crate::{Widget, WidgetContext, config::TextOptions, widget::WidgetStyle};
+
+/// Fixed text, which scripts can replace over the control socket.
+pub struct TextWidget {
+    text: String,
+    style: WidgetStyle,
+}
+
+impl TextWidget {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            style: WidgetStyle::default(),
+        }
+    }
+
+    pub fn with_style(mut self, style: WidgetStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_options(mut self, options: TextOptions) -> Self {
+        self.text = options.text;
+        self
+    }
+}
+
+impl Default for TextWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Widget for TextWidget {
+    fn update(&mut self, _ctx: &WidgetContext<'_>) {}
+
+    fn paint(&mut self, ui: &mut egui::Ui) {
+        if !self.text.is_empty() {
+            ui.label(self.style.text(self.text.as_str()));
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> bool {
+        text.clone_into(&mut self.text);
+        true
+    }
+}
@@ -1,4 +1,4 @@
-use std::ptr::null_mut;
+use std::ptr::{null, null_mut};
 
 use windows_sys::Win32::{
     Foundation::{HWND, LPARAM, LRESULT, WPARAM},
@@ -32,16 +32,19 @@ use windows_sys::Win32::{
     System::Com::{COINIT_APARTMENTTHREADED, CoInitializeEx, CoUninitialize},
     UI::WindowsAndMessaging::{
         CS_HREDRAW, CS_VREDRAW, CreateWindowExW, DefWindowProcW, DispatchMessageW,
-        GetSystemMetrics, HWND_TOPMOST, MSG, PM_REMOVE, PeekMessageW, PostQuitMessage,
-        RegisterClassW, SM_CXSCREEN, SM_CYSCREEN, SW_SHOW, SWP_NOACTIVATE, SWP_NOOWNERZORDER,
-        SWP_NOSENDCHANGING, SWP_SHOWWINDOW, SetWindowPos, ShowWindow, TranslateMessage, WM_DESTROY,
-        WNDCLASSW, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
+        GetSystemMetrics, HWND_TOPMOST, MSG, MsgWaitForMultipleObjects, PM_REMOVE, PeekMessageW,
+        PostQuitMessage, QS_ALLINPUT, RegisterClassW, SM_CXSCREEN, SM_CYSCREEN, SW_SHOW,
+        SWP_NOACTIVATE, SWP_NOOWNERZORDER, SWP_NOSENDCHANGING, SWP_SHOWWINDOW, SetWindowPos,
+        ShowWindow, TranslateMessage, WM_DESTROY, WNDCLASSW, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW,
+        WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_POPUP,
     },
 };
 
-use crate::FpsTracker;
-
 const D3D_DRIVER_TYPE_HARDWARE: i32 = 1;
+const INFINITE: u32 = u32::MAX;
+/// Nothing feeds frame timings on Windows yet: the window's own presents
+/// follow no game, so it says so instead of showing statistics.
+const NOTICE: &str = "rs_overlay: no frame source on Windows";
 
 pub fn run() -> anyhow::Result<()> {
     unsafe { CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED) };
@@ -100,11 +103,13 @@ pub fn run() -> anyhow::Result<()> {
     }
 
     let mut gfx = D3DState::new(hwnd, width as u32, height as u32)?;
-    let mut fps_tracker = FpsTracker::new();
+    // The text never changes, so one present is enough.
+    gfx.render(NOTICE);
 
     'running: loop {
         let mut msg = MSG::default();
         unsafe {
+            MsgWaitForMultipleObjects(0, null(), 0, INFINITE, QS_ALLINPUT);
             while PeekMessageW(&mut msg, 0, 0, 0, PM_REMOVE) != 0 {
                 if msg.message == WM_DESTROY {
                     break 'running;
@@ -113,9 +118,6 @@ pub fn run() -> anyhow::Result<()> {
                 DispatchMessageW(&msg);
             }
         }
-
-        fps_tracker.tick();
-        gfx.render(fps_tracker.fps());
     }
 
     Ok(())
@@ -281,7 +283,7 @@ impl D3DState {
         })
     }
 
-    fn render(&mut self, fps: f32) {
+    fn render(&mut self, text: &str) {
         unsafe {
             let clear = [0.0, 0.0, 0.0, 0.0];
             (*self.context).ClearRenderTargetView(self.rtv, clear.as_ptr());
@@ -295,8 +297,7 @@ impl D3DState {
             };
             (*self.d2d_context).Clear(&clear_color);
 
-            let text = format!("FPS: {:.1}", fps);
-            let text_w = widestring::U16CString::from_str(text).expect("fps text");
+            let text_w = widestring::U16CString::from_str(text).expect("overlay text");
             let rect = D2D1_RECT_F {
                 left: 12.0,
                 top: 12.0,
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use x11rb::{
+    NONE,
+    connection::Connection as _,
+    protocol::{
+        Event,
+        xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{Atoms, wait_readable, window_matches};
+use crate::config::WindowSelector;
+
+/// The focused window according to `_NET_ACTIVE_WINDOW`, kept current by
+/// property notifications on the root window.
+pub(crate) struct ActiveWindow {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    active: Option<Window>,
+}
+
+impl ActiveWindow {
+    pub(crate) fn connect() -> anyhow::Result<Self> {
+        let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?
+        .check()
+        .context("watch the focused window")?;
+        let mut active = Self {
+            conn,
+            root,
+            atoms,
+            active: None,
+        };
+        active.refresh()?;
+        Ok(active)
+    }
+
+    /// Waits up to `timeout` for the focus to move or the focused window's
+    /// properties to change. Returns whether anything did.
+    pub(crate) fn wait(&mut self, timeout: Duration) -> anyhow::Result<bool> {
+        self.conn.flush()?;
+        wait_readable(&self.conn, timeout);
+        let mut focus_moved = false;
+        let mut changed = false;
+        while let Some(event) = self.conn.poll_for_event()? {
+            match event {
+                Event::PropertyNotify(event) if event.window == self.root => {
+                    focus_moved |= event.atom == self.atoms._NET_ACTIVE_WINDOW;
+                }
+                Event::PropertyNotify(event) if Some(event.window) == self.active => {
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        if focus_moved {
+            self.refresh()?;
+        }
+        Ok(focus_moved || changed)
+    }
+
+    pub(crate) fn matches(&self, selector: &WindowSelector) -> bool {
+        self.active
+            .is_some_and(|window| window_matches(&self.conn, &self.atoms, window, selector))
+    }
+
+    /// Whether the focused window has `_NET_WM_STATE_FULLSCREEN`.
+    pub(crate) fn is_fullscreen(&self) -> bool {
+        let Some(window) = self.active else {
+            return false;
+        };
+        let state = self
+            .conn
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                64,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok());
+        state.is_some_and(|state| {
+            state.value32().is_some_and(|mut atoms| {
+                atoms.any(|atom| atom == self.atoms._NET_WM_STATE_FULLSCREEN)
+            })
+        })
+    }
+
+    fn refresh(&mut self) -> anyhow::Result<()> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?;
+        let active = reply
+            .value32()
+            .and_then(|mut windows| windows.next())
+            .filter(|window| *window != NONE);
+        if active == self.active {
+            return Ok(());
+        }
+        // Errors for windows that are already gone arrive as events and are
+        // ignored there.
+        if let Some(previous) = self.active {
+            self.conn.change_window_attributes(
+                previous,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+            )?;
+        }
+        if let Some(window) = active {
+            self.conn.change_window_attributes(
+                window,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )?;
+        }
+        self.active = active;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use std::ptr::null_mut;
+
+use anyhow::Context as _;
+use x11rb::{
+    connection::{Connection as _, RequestConnection as _},
+    protocol::{
+        shm::{self, ConnectionExt as _},
+        xproto::{ConnectionExt as _, CreateGCAux, Gcontext, ImageFormat, ImageOrder, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::render::Canvas;
+
+/// Size of the `PutImage` request header, which counts against the maximum
+/// request length.
+const PUT_IMAGE_HEADER: usize = 24;
+
+/// Copies software-rendered frames into an X11 window, through MIT-SHM when
+/// the server shares memory with us and plain `PutImage` otherwise.
+pub(crate) struct WindowBlitter {
+    conn: RustConnection,
+    window: Window,
+    gc: Gcontext,
+    depth: u8,
+    byte_order: ImageOrder,
+    shm: Shm,
+    buffer: Vec<u8>,
+}
+
+enum Shm {
+    /// The server lacks MIT-SHM or cannot attach our segments, as on a
+    /// remote display.
+    Unavailable,
+    Detached,
+    Attached(ShmSegment),
+}
+
+struct ShmSegment {
+    seg: shm::Seg,
+    addr: *mut u8,
+    len: usize,
+}
+
+impl WindowBlitter {
+    pub(crate) fn new(window: Window) -> anyhow::Result<Self> {
+        let (conn, _) = RustConnection::connect(None).context("connect to X server")?;
+        let attributes = conn
+            .get_window_attributes(window)?
+            .reply()
+            .context("query overlay window")?;
+        let depth = conn.get_geometry(window)?.reply()?.depth;
+        let setup = conn.setup();
+        let visual = setup
+            .roots
+            .iter()
+            .flat_map(|screen| &screen.allowed_depths)
+            .flat_map(|allowed| &allowed.visuals)
+            .find(|visual| visual.visual_id == attributes.visual)
+            .context("overlay window has an unknown visual")?;
+        let packs_argb32 = setup
+            .pixmap_formats
+            .iter()
+            .any(|format| format.depth == depth && format.bits_per_pixel == 32);
+        if !packs_argb32
+            || visual.red_mask != 0xff_0000
+            || visual.green_mask != 0x00_ff00
+            || visual.blue_mask != 0x00_00ff
+        {
+            anyhow::bail!("the software renderer needs a 24- or 32-bit RGB visual");
+        }
+        let byte_order = setup.image_byte_order;
+
+        let shm = match conn.extension_information(shm::X11_EXTENSION_NAME)? {
+            Some(_) => Shm::Detached,
+            None => Shm::Unavailable,
+        };
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, &CreateGCAux::new())?;
+
+        Ok(Self {
+            conn,
+            window,
+            gc,
+            depth,
+            byte_order,
+            shm,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub(crate) fn present(&mut self, canvas: &Canvas) -> anyhow::Result<()> {
+        if canvas.width == 0 || canvas.height == 0 {
+            return Ok(());
+        }
+        let len = canvas.pixels.len() * 4;
+        self.buffer.resize(len, 0);
+        for (bytes, pixel) in self.buffer.chunks_exact_mut(4).zip(&canvas.pixels) {
+            let [r, g, b, a] = pixel.to_array();
+            let argb = u32::from_be_bytes([a, r, g, b]);
+            bytes.copy_from_slice(&match self.byte_order {
+                ImageOrder::MSB_FIRST => argb.to_be_bytes(),
+                _ => argb.to_le_bytes(),
+            });
+        }
+
+        if let Some((seg, addr)) = self
+            .shm_segment(len)
+            .map(|segment| (segment.seg, segment.addr))
+        {
+            unsafe { std::ptr::copy_nonoverlapping(self.buffer.as_ptr(), addr, len) };
+            let (width, height) = (canvas.width as u16, canvas.height as u16);
+            self.conn.shm_put_image(
+                self.window,
+                self.gc,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                self.depth,
+                ImageFormat::Z_PIXMAP.into(),
+                false,
+                seg,
+                0,
+            )?;
+            // The segment is rewritten next frame; wait until the server has
+            // read this one.
+            self.conn.get_input_focus()?.reply()?;
+            return Ok(());
+        }
+
+        let row_len = canvas.width as usize * 4;
+        let max_rows = ((self.conn.maximum_request_bytes() - PUT_IMAGE_HEADER) / row_len).max(1);
+        for (chunk, rows) in self.buffer.chunks(row_len * max_rows).enumerate() {
+            self.conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                self.window,
+                self.gc,
+                canvas.width as u16,
+                (rows.len() / row_len) as u16,
+                0,
+                (chunk * max_rows) as i16,
+                0,
+                self.depth,
+                rows,
+            )?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// A segment of at least `len` bytes, or `None` when MIT-SHM cannot be
+    /// used.
+    fn shm_segment(&mut self, len: usize) -> Option<&ShmSegment> {
+        let too_small = match &self.shm {
+            Shm::Unavailable => return None,
+            Shm::Detached => true,
+            Shm::Attached(segment) => segment.len < len,
+        };
+        if too_small {
+            if let Shm::Attached(old) = std::mem::replace(&mut self.shm, Shm::Detached) {
+                self.detach(old);
+            }
+            self.shm = match attach_segment(&self.conn, len) {
+                Ok(segment) => Shm::Attached(segment),
+                Err(_) => Shm::Unavailable,
+            };
+        }
+        match &self.shm {
+            Shm::Attached(segment) => Some(segment),
+            _ => None,
+        }
+    }
+
+    fn detach(&self, segment: ShmSegment) {
+        let _ = self.conn.shm_detach(segment.seg);
+        unsafe { libc::shmdt(segment.addr.cast()) };
+    }
+}
+
+impl Drop for WindowBlitter {
+    fn drop(&mut self) {
+        if let Shm::Attached(segment) = std::mem::replace(&mut self.shm, Shm::Unavailable) {
+            self.detach(segment);
+        }
+        let _ = self.conn.free_gc(self.gc);
+        let _ = self.conn.flush();
+    }
+}
+
+fn attach_segment(conn: &RustConnection, len: usize) -> anyhow::Result<ShmSegment> {
+    let id = unsafe { libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600) };
+    if id < 0 {
+        return Err(std::io::Error::last_os_error()).context("create shared memory segment");
+    }
+    let addr = unsafe { libc::shmat(id, std::ptr::null(), 0) };
+    let attached = if addr as isize == -1 {
+        Err(anyhow::Error::from(std::io::Error::last_os_error()))
+    } else {
+        let attach = || -> anyhow::Result<shm::Seg> {
+            let seg = conn.generate_id()?;
+            conn.shm_attach(seg, id as u32, false)?.check()?;
+            Ok(seg)
+        };
+        attach()
+            .map(|seg| ShmSegment {
+                seg,
+                addr: addr.cast(),
+                len,
+            })
+            .inspect_err(|_| unsafe {
+                libc::shmdt(addr);
+            })
+    };
+    // Both sides have attached by now, so the segment goes away with them.
+    unsafe { libc::shmctl(id, libc::IPC_RMID, null_mut()) };
+    attached
+}
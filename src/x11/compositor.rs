@@ -0,0 +1,99 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use winit::event_loop::EventLoopProxy;
+use x11rb::{
+    NONE,
+    connection::{Connection as _, RequestConnection as _},
+    protocol::{
+        Event,
+        xfixes::{self, ConnectionExt as _, SelectionEventMask},
+        xproto::{Atom, ConnectionExt as _},
+    },
+    rust_connection::RustConnection,
+};
+
+use super::wait_readable;
+use crate::event::OverlayEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sends `OverlayEvent::Compositing` with whether a compositing manager owns
+/// `_NET_WM_CM_S<screen>`, once right away and again whenever one starts or
+/// stops, until `stop` is set.
+pub(crate) fn spawn_compositor_watcher(
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+    if conn
+        .extension_information(xfixes::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        anyhow::bail!("the X server lacks the XFixes extension");
+    }
+    // XFixes requests fail until the client has announced its version.
+    conn.xfixes_query_version(5, 0)?.reply()?;
+    let root = conn.setup().roots[screen].root;
+    let selection = conn
+        .intern_atom(false, format!("_NET_WM_CM_S{screen}").as_bytes())?
+        .reply()?
+        .atom;
+    conn.xfixes_select_selection_input(
+        root,
+        selection,
+        SelectionEventMask::SET_SELECTION_OWNER
+            | SelectionEventMask::SELECTION_WINDOW_DESTROY
+            | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+    )?
+    .check()
+    .context("watch the compositing manager")?;
+
+    thread::Builder::new()
+        .name("rs_overlay-x11-compositor".to_owned())
+        .spawn(move || {
+            if let Err(err) = run(&conn, selection, &proxy, &stop) {
+                let _ = proxy.send_event(OverlayEvent::Status(format!(
+                    "watching the compositing manager: {err:#}"
+                )));
+            }
+        })
+        .context("spawn compositor watcher")
+}
+
+fn run(
+    conn: &RustConnection,
+    selection: Atom,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    let mut reported = None;
+    let mut changed = true;
+    while !stop.load(Ordering::Relaxed) {
+        if changed {
+            changed = false;
+            let composited = conn.get_selection_owner(selection)?.reply()?.owner != NONE;
+            if reported != Some(composited) {
+                reported = Some(composited);
+                if proxy
+                    .send_event(OverlayEvent::Compositing(composited))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+        wait_readable(conn, POLL_INTERVAL);
+        while let Some(event) = conn.poll_for_event()? {
+            changed |= matches!(event, Event::XfixesSelectionNotify(_));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::EventLoopProxy,
+};
+use x11rb::{
+    connection::Connection as _,
+    protocol::{
+        Event,
+        xproto::{
+            ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, EventMask, MapState,
+            StackMode, Window,
+        },
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{Atoms, find_window, wait_readable};
+use crate::{
+    config::WindowSelector,
+    event::{OverlayEvent, TargetGeometry},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reports the geometry of the window `selector` picks to the event loop
+/// and keeps `overlay` stacked just above it, until `stop` is set. Waits for
+/// the window to appear, and for the next match once it is destroyed.
+pub(crate) fn spawn_window_follower(
+    selector: WindowSelector,
+    overlay: Window,
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+    let root = conn.setup().roots[screen].root;
+    let atoms = Atoms::new(&conn)?.reply()?;
+
+    thread::Builder::new()
+        .name("rs_overlay-x11-follow".to_owned())
+        .spawn(move || {
+            let follower = Follower {
+                conn: &conn,
+                root,
+                overlay,
+                proxy: &proxy,
+            };
+            if let Err(err) = follower.run(&atoms, &selector, &stop) {
+                let _ =
+                    proxy.send_event(OverlayEvent::Status(format!("following a window: {err:#}")));
+            }
+        })
+        .context("spawn window follower")
+}
+
+struct Follower<'a> {
+    conn: &'a RustConnection,
+    root: Window,
+    overlay: Window,
+    proxy: &'a EventLoopProxy<OverlayEvent>,
+}
+
+impl Follower<'_> {
+    fn run(
+        &self,
+        atoms: &Atoms,
+        selector: &WindowSelector,
+        stop: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        let mut waiting_reported = false;
+        while !stop.load(Ordering::Relaxed) {
+            let Some(target) = find_window(self.conn, self.root, atoms, selector)? else {
+                if !waiting_reported {
+                    waiting_reported = true;
+                    self.send(OverlayEvent::Status(
+                        "waiting for a window matching [window.follow]".to_owned(),
+                    ));
+                }
+                thread::sleep(SEARCH_INTERVAL);
+                continue;
+            };
+            waiting_reported = false;
+            self.send(OverlayEvent::Status(format!(
+                "following window {target:#x}"
+            )));
+            self.track(target, stop)?;
+            self.send(OverlayEvent::Target(TargetGeometry::Hidden));
+        }
+        Ok(())
+    }
+
+    /// Returns once `target` is destroyed or reparented, or `stop` is set.
+    fn track(&self, target: Window, stop: &AtomicBool) -> anyhow::Result<()> {
+        // Moves and restacks happen to the window manager's frame, which
+        // reports them; the client window only hears about its own size.
+        let Some(frame) = self.top_level(target)? else {
+            return Ok(());
+        };
+        let watch = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+        self.conn.change_window_attributes(target, &watch)?;
+        if frame != target {
+            self.conn.change_window_attributes(frame, &watch)?;
+        }
+
+        let mut reported = None;
+        let mut changed = true;
+        while !stop.load(Ordering::Relaxed) {
+            if changed {
+                changed = false;
+                let Some(geometry) = self.geometry(target)? else {
+                    return Ok(());
+                };
+                if reported != Some(geometry) {
+                    reported = Some(geometry);
+                    self.send(OverlayEvent::Target(geometry));
+                }
+                if geometry != TargetGeometry::Hidden {
+                    self.stack_above(frame)?;
+                }
+            }
+            self.conn.flush()?;
+            wait_readable(self.conn, POLL_INTERVAL);
+            while let Some(event) = self.conn.poll_for_event()? {
+                match event {
+                    Event::DestroyNotify(event) if event.window == target => return Ok(()),
+                    // The window manager restarted or let go of the window;
+                    // start over to find its new frame.
+                    Event::ReparentNotify(event) if event.window == target => return Ok(()),
+                    Event::ConfigureNotify(_) | Event::MapNotify(_) | Event::UnmapNotify(_) => {
+                        changed = true;
+                    }
+                    // The window went away between lookup and selecting events.
+                    Event::Error(_) => return Ok(()),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The child of the root window that contains `window`. `None` once the
+    /// window is gone.
+    fn top_level(&self, mut window: Window) -> anyhow::Result<Option<Window>> {
+        loop {
+            let Ok(tree) = self.conn.query_tree(window)?.reply() else {
+                return Ok(None);
+            };
+            if tree.parent == self.root || tree.parent == x11rb::NONE {
+                return Ok(Some(window));
+            }
+            window = tree.parent;
+        }
+    }
+
+    /// Where the client area of `target` is on the root window. `None` once
+    /// the window is gone.
+    fn geometry(&self, target: Window) -> anyhow::Result<Option<TargetGeometry>> {
+        let attributes = self.conn.get_window_attributes(target)?;
+        let geometry = self.conn.get_geometry(target)?;
+        let origin = self.conn.translate_coordinates(target, self.root, 0, 0)?;
+        let (Ok(attributes), Ok(geometry), Ok(origin)) =
+            (attributes.reply(), geometry.reply(), origin.reply())
+        else {
+            return Ok(None);
+        };
+        if attributes.map_state != MapState::VIEWABLE {
+            return Ok(Some(TargetGeometry::Hidden));
+        }
+        Ok(Some(TargetGeometry::Visible {
+            position: PhysicalPosition::new(origin.dst_x.into(), origin.dst_y.into()),
+            size: PhysicalSize::new(geometry.width.into(), geometry.height.into()),
+        }))
+    }
+
+    /// A window manager may decline the request, in which case the overlay
+    /// keeps the stacking it has.
+    fn stack_above(&self, frame: Window) -> anyhow::Result<()> {
+        self.conn.configure_window(
+            self.overlay,
+            &ConfigureWindowAux::new()
+                .sibling(frame)
+                .stack_mode(StackMode::ABOVE),
+        )?;
+        Ok(())
+    }
+
+    fn send(&self, event: OverlayEvent) {
+        let _ = self.proxy.send_event(event);
+    }
+}
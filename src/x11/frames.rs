@@ -0,0 +1,152 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use x11rb::{
+    NONE,
+    connection::{Connection as _, RequestConnection as _},
+    protocol::{
+        Event, damage,
+        damage::ConnectionExt as _,
+        present,
+        present::ConnectionExt as _,
+        xfixes::ConnectionExt as _,
+        xproto::{ChangeWindowAttributesAux, ConnectionExt as _, EventMask, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{Atoms, find_window, wait_readable};
+use crate::{
+    config::{CaptureMethod, X11SourceOptions},
+    source::{SourceEvent, instant_from_monotonic},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts frames presented by the window `options` selects until `stop` is
+/// set, re-attaching whenever the window is destroyed and reappears.
+pub(crate) fn spawn_frame_counter(
+    options: X11SourceOptions,
+    events: Sender<SourceEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    thread::Builder::new()
+        .name("rs_overlay-x11-frames".to_owned())
+        .spawn(move || {
+            if let Err(err) = run(&options, &events, &stop) {
+                let _ = events.send(SourceEvent::Status(format!("x11 frame source: {err:#}")));
+            }
+        })
+        .context("spawn x11 frame counter")
+}
+
+fn run(
+    options: &X11SourceOptions,
+    events: &Sender<SourceEvent>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+    let root = conn.setup().roots[screen].root;
+    let atoms = Atoms::new(&conn)?.reply()?;
+    let method = negotiate(&conn, options.method)?;
+
+    let mut waiting_reported = false;
+    while !stop.load(Ordering::Relaxed) {
+        let Some(window) = find_window(&conn, root, &atoms, &options.window)? else {
+            if !waiting_reported {
+                waiting_reported = true;
+                let _ = events.send(SourceEvent::Status(
+                    "waiting for a window matching [source]".to_owned(),
+                ));
+            }
+            thread::sleep(SEARCH_INTERVAL);
+            continue;
+        };
+        waiting_reported = false;
+        let _ = events.send(SourceEvent::Status(format!(
+            "measuring frames of window {window:#x}"
+        )));
+        count_frames(&conn, window, method, events, stop)?;
+    }
+    Ok(())
+}
+
+/// Checks the extensions `requested` needs, falling back from Present to
+/// Damage when the server has no Present support.
+fn negotiate(conn: &RustConnection, requested: CaptureMethod) -> anyhow::Result<CaptureMethod> {
+    if requested == CaptureMethod::Present
+        && conn
+            .extension_information(present::X11_EXTENSION_NAME)?
+            .is_some()
+    {
+        conn.present_query_version(1, 0)?.reply()?;
+        return Ok(CaptureMethod::Present);
+    }
+
+    if conn
+        .extension_information(damage::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        anyhow::bail!("the X server supports neither Present nor Damage");
+    }
+    conn.xfixes_query_version(5, 0)?.reply()?;
+    conn.damage_query_version(1, 1)?.reply()?;
+    Ok(CaptureMethod::Damage)
+}
+
+/// Returns once `window` is destroyed or `stop` is set.
+fn count_frames(
+    conn: &RustConnection,
+    window: Window,
+    method: CaptureMethod,
+    events: &Sender<SourceEvent>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    conn.change_window_attributes(
+        window,
+        &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+    )?;
+    let id = conn.generate_id()?;
+    match method {
+        CaptureMethod::Present => {
+            conn.present_select_input(id, window, present::EventMask::COMPLETE_NOTIFY)?;
+        }
+        CaptureMethod::Damage => {
+            conn.damage_create(id, window, damage::ReportLevel::NON_EMPTY)?;
+        }
+    }
+    conn.flush()?;
+
+    while !stop.load(Ordering::Relaxed) {
+        while let Some(event) = conn.poll_for_event()? {
+            match event {
+                Event::PresentCompleteNotify(event)
+                    if event.window == window && event.kind == present::CompleteKind::PIXMAP =>
+                {
+                    let at = instant_from_monotonic(Duration::from_micros(event.ust));
+                    let _ = events.send(SourceEvent::Frame(at));
+                }
+                Event::DamageNotify(event) if event.damage == id => {
+                    let _ = events.send(SourceEvent::Frame(Instant::now()));
+                    conn.damage_subtract(id, NONE, NONE)?;
+                }
+                Event::DestroyNotify(event) if event.window == window => return Ok(()),
+                // The window went away between lookup and selecting events.
+                Event::Error(_) => return Ok(()),
+                _ => {}
+            }
+        }
+        conn.flush()?;
+        wait_readable(conn, POLL_INTERVAL);
+    }
+    Ok(())
+}
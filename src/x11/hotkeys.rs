@@ -0,0 +1,232 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use winit::event_loop::EventLoopProxy;
+use x11rb::{
+    connection::Connection as _,
+    errors::ReplyError,
+    protocol::{
+        Event,
+        xproto::{ConnectionExt as _, GrabMode, Keycode, Keysym, ModMask, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+use super::wait_readable;
+use crate::{
+    config::{HotkeyAction, KeyBinding, KeyModifier},
+    event::OverlayEvent,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const NUM_LOCK: Keysym = 0xff7f;
+const SCROLL_LOCK: Keysym = 0xff14;
+/// Shift, Lock, Control and Mod1 to Mod5; the rest of a key event's state
+/// are pointer buttons.
+const MODIFIER_BITS: u16 = 0xff;
+
+struct Grab {
+    keycode: Keycode,
+    modifiers: u16,
+    action: HotkeyAction,
+}
+
+/// Grabs `bindings` on the root window and reports their presses until
+/// `stop` is set. Bindings that cannot be grabbed are reported and skipped.
+///
+/// The lock modifiers are part of a key's state, so each binding is grabbed
+/// once for every combination of Caps Lock, Num Lock and Scroll Lock, and
+/// they are masked out when matching.
+pub(crate) fn spawn_hotkey_grabber(
+    bindings: Vec<(KeyBinding, HotkeyAction)>,
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+    let root = conn.setup().roots[screen].root;
+    let keymap = Keymap::query(&conn)?;
+    let locks = u16::from(ModMask::LOCK)
+        | keymap.modifier_of(&conn, NUM_LOCK)?
+        | keymap.modifier_of(&conn, SCROLL_LOCK)?;
+
+    let mut grabs = Vec::new();
+    for (binding, action) in bindings {
+        let keycodes = keymap.keycodes(binding.keysym);
+        if keycodes.is_empty() {
+            let _ = proxy.send_event(OverlayEvent::Status(format!(
+                "no key on this keyboard produces hotkey {binding}"
+            )));
+            continue;
+        }
+        let modifiers = modifier_mask(&binding.modifiers);
+        match grab(&conn, root, &keycodes, modifiers, locks) {
+            Ok(()) => grabs.extend(keycodes.into_iter().map(|keycode| Grab {
+                keycode,
+                modifiers,
+                action,
+            })),
+            Err(_) => {
+                let _ = proxy.send_event(OverlayEvent::Status(format!(
+                    "hotkey {binding} is taken by another program"
+                )));
+            }
+        }
+    }
+
+    thread::Builder::new()
+        .name("rs_overlay-x11-hotkeys".to_owned())
+        .spawn(move || {
+            if let Err(err) = run(&conn, &grabs, locks, &proxy, &stop) {
+                let _ = proxy.send_event(OverlayEvent::Status(format!("hotkeys: {err:#}")));
+            }
+        })
+        .context("spawn hotkey grabber")
+}
+
+/// Grabs every keycode with every subset of the lock bits, or none of them:
+/// when one is taken, the ones already grabbed are released again.
+fn grab(
+    conn: &RustConnection,
+    root: Window,
+    keycodes: &[Keycode],
+    modifiers: u16,
+    locks: u16,
+) -> Result<(), ReplyError> {
+    let mut grabbed = Vec::new();
+    for &keycode in keycodes {
+        let mut held = locks;
+        // Every subset of the lock bits, down to none.
+        loop {
+            let mask = ModMask::from(modifiers | held);
+            let result = conn
+                .grab_key(false, root, mask, keycode, GrabMode::ASYNC, GrabMode::ASYNC)
+                .map_err(ReplyError::from)
+                .and_then(|cookie| cookie.check());
+            if let Err(err) = result {
+                for (keycode, mask) in grabbed {
+                    let _ = conn.ungrab_key(keycode, root, mask);
+                }
+                return Err(err);
+            }
+            grabbed.push((keycode, mask));
+            if held == 0 {
+                break;
+            }
+            held = (held - 1) & locks;
+        }
+    }
+    Ok(())
+}
+
+fn run(
+    conn: &RustConnection,
+    grabs: &[Grab],
+    locks: u16,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    while !stop.load(Ordering::Relaxed) {
+        conn.flush()?;
+        wait_readable(conn, POLL_INTERVAL);
+        let mut events = Vec::new();
+        while let Some(event) = conn.poll_for_event()? {
+            events.push(event);
+        }
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            match event {
+                // Auto-repeat sends a release and a press with the same time
+                // while the key is held; neither is a new press.
+                Event::KeyRelease(release) => {
+                    if let Some(Event::KeyPress(press)) = events.peek()
+                        && press.detail == release.detail
+                        && press.time == release.time
+                    {
+                        events.next();
+                    }
+                }
+                Event::KeyPress(press) => {
+                    let modifiers = u16::from(press.state) & MODIFIER_BITS & !locks;
+                    if let Some(grab) = grabs
+                        .iter()
+                        .find(|grab| grab.keycode == press.detail && grab.modifiers == modifiers)
+                        && proxy.send_event(OverlayEvent::Hotkey(grab.action)).is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn modifier_mask(modifiers: &[KeyModifier]) -> u16 {
+    modifiers
+        .iter()
+        .map(|modifier| {
+            u16::from(match modifier {
+                KeyModifier::Shift => ModMask::SHIFT,
+                KeyModifier::Ctrl => ModMask::CONTROL,
+                KeyModifier::Alt => ModMask::M1,
+                KeyModifier::Super => ModMask::M4,
+                KeyModifier::AltGr => ModMask::M5,
+            })
+        })
+        .fold(0, |mask, bit| mask | bit)
+}
+
+/// The keysyms each keycode produces.
+struct Keymap {
+    first_keycode: Keycode,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<Keysym>,
+}
+
+impl Keymap {
+    fn query(conn: &RustConnection) -> anyhow::Result<Self> {
+        let setup = conn.setup();
+        let first_keycode = setup.min_keycode;
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let reply = conn
+            .get_keyboard_mapping(first_keycode, count)?
+            .reply()
+            .context("read the keyboard mapping")?;
+        Ok(Self {
+            first_keycode,
+            keysyms_per_keycode: usize::from(reply.keysyms_per_keycode).max(1),
+            keysyms: reply.keysyms,
+        })
+    }
+
+    /// Keycodes producing `keysym` with or without Shift.
+    fn keycodes(&self, keysym: Keysym) -> Vec<Keycode> {
+        self.keysyms
+            .chunks(self.keysyms_per_keycode)
+            .enumerate()
+            .filter(|(_, syms)| syms.iter().take(2).any(|sym| *sym == keysym))
+            .map(|(index, _)| self.first_keycode + index as Keycode)
+            .collect()
+    }
+
+    /// The modifier bit a key producing `keysym` sets, or 0 when none does.
+    fn modifier_of(&self, conn: &RustConnection, keysym: Keysym) -> anyhow::Result<u16> {
+        let keycodes = self.keycodes(keysym);
+        let reply = conn.get_modifier_mapping()?.reply()?;
+        let per_modifier = usize::from(reply.keycodes_per_modifier()).max(1);
+        Ok(reply
+            .keycodes
+            .chunks(per_modifier)
+            .enumerate()
+            .filter(|(_, modifier)| modifier.iter().any(|keycode| keycodes.contains(keycode)))
+            .fold(0, |mask, (bit, _)| mask | 1 << bit))
+    }
+}
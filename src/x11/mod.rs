@@ -0,0 +1,62 @@
+mod active;
+mod blit;
+mod compositor;
+mod follow;
+mod frames;
+mod hotkeys;
+mod monitors;
+mod shape;
+mod stacking;
+mod window;
+
+use std::{os::fd::AsRawFd as _, time::Duration};
+
+use x11rb::rust_connection::RustConnection;
+
+pub(crate) use active::ActiveWindow;
+pub(crate) use blit::WindowBlitter;
+pub(crate) use compositor::spawn_compositor_watcher;
+pub(crate) use follow::spawn_window_follower;
+pub(crate) use frames::spawn_frame_counter;
+pub(crate) use hotkeys::spawn_hotkey_grabber;
+pub(crate) use monitors::spawn_monitor_watcher;
+pub(crate) use shape::WindowShaper;
+pub(crate) use stacking::StackKeeper;
+pub(crate) use window::{Atoms, find_window, window_matches};
+
+/// Whether winit talks to an X server rather than a Wayland compositor.
+pub(crate) fn is_x11<T>(target: &winit::event_loop::EventLoopWindowTarget<T>) -> bool {
+    use winit::raw_window_handle::{HasDisplayHandle as _, RawDisplayHandle};
+
+    target.display_handle().is_ok_and(|handle| {
+        matches!(
+            handle.as_raw(),
+            RawDisplayHandle::Xlib(_) | RawDisplayHandle::Xcb(_)
+        )
+    })
+}
+
+/// The X11 id of a winit window, or `None` when it is not an X11 window.
+pub(crate) fn window_xid(window: &winit::window::Window) -> Option<u32> {
+    use winit::raw_window_handle::{HasWindowHandle as _, RawWindowHandle};
+
+    match window.window_handle().ok()?.as_raw() {
+        RawWindowHandle::Xlib(handle) => Some(handle.window as u32),
+        RawWindowHandle::Xcb(handle) => Some(handle.window.get()),
+        _ => None,
+    }
+}
+
+/// Waits until the X server has sent something or `timeout` passes, so
+/// background threads can poll a stop flag without spinning.
+pub(crate) fn wait_readable(conn: &RustConnection, timeout: Duration) {
+    let mut fd = libc::pollfd {
+        fd: conn.stream().as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    unsafe {
+        libc::poll(&mut fd, 1, timeout_ms);
+    }
+}
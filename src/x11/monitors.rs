@@ -0,0 +1,80 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use winit::event_loop::EventLoopProxy;
+use x11rb::{
+    connection::{Connection as _, RequestConnection as _},
+    protocol::{
+        Event,
+        randr::{self, ConnectionExt as _, NotifyMask},
+    },
+    rust_connection::RustConnection,
+};
+
+use super::wait_readable;
+use crate::event::OverlayEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// A mode switch or hotplug arrives as a burst of notifications; the event
+/// loop hears about it once they stop.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Sends `OverlayEvent::MonitorsChanged` whenever RandR reports a new screen
+/// configuration, until `stop` is set.
+///
+/// Only screen-change notifications count: they are what winit refreshes
+/// its monitor list on, so the list is current by the time the event loop
+/// asks for it.
+pub(crate) fn spawn_monitor_watcher(
+    proxy: EventLoopProxy<OverlayEvent>,
+    stop: Arc<AtomicBool>,
+) -> anyhow::Result<JoinHandle<()>> {
+    let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        anyhow::bail!("the X server lacks the RandR extension");
+    }
+    let root = conn.setup().roots[screen].root;
+    conn.randr_select_input(root, NotifyMask::SCREEN_CHANGE)?
+        .check()
+        .context("watch monitor changes")?;
+
+    thread::Builder::new()
+        .name("rs_overlay-x11-monitors".to_owned())
+        .spawn(move || {
+            let _ = run(&conn, &proxy, &stop);
+        })
+        .context("spawn monitor watcher")
+}
+
+fn run(
+    conn: &RustConnection,
+    proxy: &EventLoopProxy<OverlayEvent>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    let mut changed_at: Option<Instant> = None;
+    while !stop.load(Ordering::Relaxed) {
+        wait_readable(conn, POLL_INTERVAL);
+        while let Some(event) = conn.poll_for_event()? {
+            if let Event::RandrScreenChangeNotify(_) = event {
+                changed_at = Some(Instant::now());
+            }
+        }
+        if changed_at.is_some_and(|at| at.elapsed() >= SETTLE_TIME) {
+            changed_at = None;
+            if proxy.send_event(OverlayEvent::MonitorsChanged).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
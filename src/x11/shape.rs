@@ -0,0 +1,318 @@
+use anyhow::Context as _;
+use egui::epaint::Primitive;
+use x11rb::{
+    NONE,
+    connection::{Connection as _, RequestConnection as _},
+    protocol::{
+        shape::{self, ConnectionExt as _},
+        xproto::{ClipOrdering, Rectangle, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::render::{Canvas, Rasterizer};
+
+/// Alpha from which a pixel counts as covered. Without a compositor pixels
+/// show unblended, so faint antialiased edges would turn into dark fringes.
+const COVERED_ALPHA: u8 = 128;
+/// Painted parts closer than this many pixels are rasterized as one region.
+const REGION_GAP: f32 = 8.0;
+
+/// Cuts an overlay window down to the pixels its widgets cover, for X
+/// servers without a compositing manager, where the rest of a transparent
+/// window would show black.
+///
+/// The frames are rasterized on the CPU to find the covered pixels, whatever
+/// renderer draws them: only the parts the paint jobs reach, and only when
+/// the jobs changed.
+///
+/// It also sets the input shape, which is empty outside edit mode so clicks
+/// go through the window.
+pub(crate) struct WindowShaper {
+    conn: RustConnection,
+    window: Window,
+    rasterizer: Rasterizer,
+    /// One region of the frame at a time.
+    canvas: Canvas,
+    /// The frame the bounding shape was last worked out for.
+    shaped: Option<ShapedFrame>,
+    /// Whether the textures changed since, which the paint jobs do not show.
+    textures_changed: bool,
+    /// The bounding shape last set; `None` while the window is unshaped.
+    applied: Option<Vec<Rectangle>>,
+    /// The input shape last set.
+    input: Vec<Rectangle>,
+}
+
+struct ShapedFrame {
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    pixels_per_point: f32,
+    size: [u32; 2],
+}
+
+impl ShapedFrame {
+    fn matches(
+        &self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        pixels_per_point: f32,
+        size: [u32; 2],
+    ) -> bool {
+        self.pixels_per_point == pixels_per_point
+            && self.size == size
+            && self.paint_jobs.len() == paint_jobs.len()
+            && self.paint_jobs.iter().zip(paint_jobs).all(|(a, b)| {
+                a.clip_rect == b.clip_rect
+                    && match (&a.primitive, &b.primitive) {
+                        (Primitive::Mesh(a), Primitive::Mesh(b)) => a == b,
+                        // Callbacks cannot be compared.
+                        _ => false,
+                    }
+            })
+    }
+}
+
+impl WindowShaper {
+    pub(crate) fn new(window: Window) -> anyhow::Result<Self> {
+        let (conn, _) = RustConnection::connect(None).context("connect to X server")?;
+        if conn
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            anyhow::bail!("the X server lacks the SHAPE extension");
+        }
+        Ok(Self {
+            conn,
+            window,
+            rasterizer: Rasterizer::default(),
+            canvas: Canvas::new(0, 0),
+            shaped: None,
+            textures_changed: false,
+            applied: None,
+            input: Vec::new(),
+        })
+    }
+
+    /// Follows every frame's texture changes, so shaping can start at any
+    /// frame.
+    pub(crate) fn update_textures(&mut self, delta: &egui::TexturesDelta) {
+        self.rasterizer.update_textures(delta);
+        self.textures_changed |= !delta.is_empty();
+    }
+
+    /// Limits the window, `width` by `height` pixels, to what `paint_jobs`
+    /// cover.
+    pub(crate) fn shape(
+        &mut self,
+        paint_jobs: &[egui::ClippedPrimitive],
+        pixels_per_point: f32,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<()> {
+        let size = [width, height];
+        if self.applied.is_some()
+            && !self.textures_changed
+            && self
+                .shaped
+                .as_ref()
+                .is_some_and(|shaped| shaped.matches(paint_jobs, pixels_per_point, size))
+        {
+            return Ok(());
+        }
+        self.textures_changed = false;
+        self.shaped = Some(ShapedFrame {
+            paint_jobs: paint_jobs.to_vec(),
+            pixels_per_point,
+            size,
+        });
+
+        let regions = painted_regions(paint_jobs, pixels_per_point, width, height);
+        let mut rectangles = Vec::new();
+        for region in &regions {
+            self.canvas
+                .resize(region.width() as u32, region.height() as u32);
+            self.rasterizer.paint_region(
+                &mut self.canvas,
+                paint_jobs,
+                pixels_per_point,
+                region.min.to_vec2(),
+            );
+            rectangles.extend(covered_rectangles(&self.canvas, region.min));
+        }
+        if self
+            .applied
+            .as_ref()
+            .is_some_and(|applied| same_rectangles(applied, &rectangles))
+        {
+            return Ok(());
+        }
+        // Each region is banded on its own, but not side by side.
+        let ordering = match regions.len() {
+            0 | 1 => ClipOrdering::YX_BANDED,
+            _ => ClipOrdering::UNSORTED,
+        };
+        self.conn.shape_rectangles(
+            shape::SO::SET,
+            shape::SK::BOUNDING,
+            ordering,
+            self.window,
+            0,
+            0,
+            &rectangles,
+        )?;
+        self.conn.flush()?;
+        self.applied = Some(rectangles);
+        Ok(())
+    }
+
+    /// Lets the window take input only inside `rects`, given in points.
+    pub(crate) fn set_input(
+        &mut self,
+        rects: &[egui::Rect],
+        pixels_per_point: f32,
+    ) -> anyhow::Result<()> {
+        let rectangles: Vec<Rectangle> = rects
+            .iter()
+            .map(|rect| {
+                let min = (rect.min * pixels_per_point).floor();
+                let max = (rect.max * pixels_per_point).ceil();
+                Rectangle {
+                    x: min.x as i16,
+                    y: min.y as i16,
+                    width: (max.x - min.x).max(0.0) as u16,
+                    height: (max.y - min.y).max(0.0) as u16,
+                }
+            })
+            .collect();
+        if same_rectangles(&self.input, &rectangles) {
+            return Ok(());
+        }
+        self.conn.shape_rectangles(
+            shape::SO::SET,
+            shape::SK::INPUT,
+            ClipOrdering::UNSORTED,
+            self.window,
+            0,
+            0,
+            &rectangles,
+        )?;
+        self.conn.flush()?;
+        self.input = rectangles;
+        Ok(())
+    }
+
+    /// Gives the window its full extent back, for a compositor to blend.
+    pub(crate) fn unshape(&mut self) -> anyhow::Result<()> {
+        self.shaped = None;
+        if self.applied.take().is_none() {
+            return Ok(());
+        }
+        self.conn
+            .shape_mask(shape::SO::SET, shape::SK::BOUNDING, self.window, 0, 0, NONE)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// Pixel bounds of what `paint_jobs` draw, with triangles close to each
+/// other merged, so each widget usually comes out as one region. egui puts
+/// everything sharing a clip rect into one mesh, so the mesh bounds would
+/// span all widgets.
+fn painted_regions(
+    paint_jobs: &[egui::ClippedPrimitive],
+    pixels_per_point: f32,
+    width: u32,
+    height: u32,
+) -> Vec<egui::Rect> {
+    let frame =
+        egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(width as f32, height as f32));
+    let mut regions: Vec<egui::Rect> = Vec::new();
+    for job in paint_jobs {
+        let Primitive::Mesh(mesh) = &job.primitive else {
+            continue;
+        };
+        let clip = job.clip_rect.intersect(frame / pixels_per_point);
+        for triangle in mesh.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|corner| mesh.vertices[triangle[corner] as usize].pos);
+            let bounds = egui::Rect::from_points(&corners).intersect(clip);
+            if !bounds.is_positive() {
+                continue;
+            }
+            let mut bounds = egui::Rect::from_min_max(
+                (bounds.min * pixels_per_point).floor(),
+                (bounds.max * pixels_per_point).ceil(),
+            )
+            .intersect(frame);
+            // Growing may make it reach regions it did not before.
+            while let Some(index) = regions
+                .iter()
+                .position(|region| region.intersects(bounds.expand(REGION_GAP)))
+            {
+                bounds = bounds.union(regions.swap_remove(index));
+            }
+            regions.push(bounds);
+        }
+    }
+    regions
+}
+
+/// The runs of covered pixels in each row of `canvas`, whose top-left corner
+/// is at `origin` in the window, with rows whose runs match the row above
+/// merged into its rectangles. The result is YX-banded.
+fn covered_rectangles(canvas: &Canvas, origin: egui::Pos2) -> Vec<Rectangle> {
+    let mut rectangles: Vec<Rectangle> = Vec::new();
+    if canvas.width == 0 {
+        return rectangles;
+    }
+    let mut band_start = 0;
+    let mut previous = Vec::new();
+    for (y, row) in canvas
+        .pixels
+        .chunks_exact(canvas.width as usize)
+        .enumerate()
+    {
+        let runs = covered_runs(row);
+        if !runs.is_empty() && runs == previous {
+            for rectangle in &mut rectangles[band_start..] {
+                rectangle.height += 1;
+            }
+            continue;
+        }
+        band_start = rectangles.len();
+        rectangles.extend(runs.iter().map(|&(start, end)| Rectangle {
+            x: (origin.x as usize + start) as i16,
+            y: (origin.y as usize + y) as i16,
+            width: (end - start) as u16,
+            height: 1,
+        }));
+        previous = runs;
+    }
+    rectangles
+}
+
+/// `Rectangle` has no `PartialEq` without x11rb's `extra-traits`.
+fn same_rectangles(a: &[Rectangle], b: &[Rectangle]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| (a.x, a.y, a.width, a.height) == (b.x, b.y, b.width, b.height))
+}
+
+/// Half-open ranges of covered pixels, left to right.
+fn covered_runs(row: &[egui::Color32]) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (x, pixel) in row.iter().enumerate() {
+        match (pixel.a() >= COVERED_ALPHA, start) {
+            (true, None) => start = Some(x),
+            (false, Some(from)) => {
+                runs.push((from, x));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        runs.push((from, row.len()));
+    }
+    runs
+}
@@ -0,0 +1,212 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use winit::event_loop::EventLoopProxy;
+use x11rb::{
+    connection::Connection as _,
+    protocol::{
+        Event,
+        xproto::{
+            AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, EventMask,
+            MapState, StackMode, Window, WindowClass,
+        },
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{Atoms, wait_readable};
+use crate::event::OverlayEvent;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Raising at most this often keeps the overlay from fighting a window
+/// manager that insists on stacking a fullscreen window on top.
+const RAISE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Raises an overlay window again whenever a fullscreen window gets stacked
+/// over it. Dropping it stops the thread.
+pub(crate) struct StackKeeper {
+    stop: Arc<AtomicBool>,
+}
+
+impl StackKeeper {
+    pub(crate) fn start(
+        overlay: Window,
+        proxy: EventLoopProxy<OverlayEvent>,
+    ) -> anyhow::Result<Self> {
+        let (conn, screen) = RustConnection::connect(None).context("connect to X server")?;
+        let root = conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_NOTIFY),
+        )?
+        .check()
+        .context("watch the stacking order")?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        thread::Builder::new()
+            .name("rs_overlay-x11-stacking".to_owned())
+            .spawn(move || {
+                let keeper = Keeper {
+                    conn: &conn,
+                    root,
+                    atoms,
+                    overlay,
+                };
+                if let Err(err) = keeper.run(&stop_thread) {
+                    let _ = proxy.send_event(OverlayEvent::Status(format!(
+                        "keeping the overlay on top: {err:#}"
+                    )));
+                }
+            })
+            .context("spawn stacking watcher")?;
+        Ok(Self { stop })
+    }
+}
+
+impl Drop for StackKeeper {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Keeper<'a> {
+    conn: &'a RustConnection,
+    root: Window,
+    atoms: Atoms,
+    overlay: Window,
+}
+
+impl Keeper<'_> {
+    fn run(&self, stop: &AtomicBool) -> anyhow::Result<()> {
+        let mut raised: Option<Instant> = None;
+        let mut changed = true;
+        while !stop.load(Ordering::Relaxed) {
+            if changed && raised.is_none_or(|at| at.elapsed() >= RAISE_INTERVAL) {
+                changed = false;
+                if self.covered()? {
+                    raised = Some(Instant::now());
+                    self.raise()?;
+                }
+            }
+            self.conn.flush()?;
+            wait_readable(self.conn, POLL_INTERVAL);
+            while let Some(event) = self.conn.poll_for_event()? {
+                if let Event::ConfigureNotify(_) | Event::MapNotify(_) | Event::CirculateNotify(_) =
+                    event
+                {
+                    changed = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a fullscreen window covering the overlay is stacked above
+    /// it. Screen lockers cover everything too, but do not claim to be
+    /// fullscreen, so the overlay stays below them.
+    fn covered(&self) -> anyhow::Result<bool> {
+        let Some(top) = self.top_level(self.overlay)? else {
+            return Ok(false);
+        };
+        let Ok(own) = self.conn.get_geometry(top)?.reply() else {
+            return Ok(false);
+        };
+        // Bottom to top.
+        let children = self.conn.query_tree(self.root)?.reply()?.children;
+        let Some(position) = children.iter().position(|window| *window == top) else {
+            return Ok(false);
+        };
+        for &window in &children[position + 1..] {
+            let attributes = self.conn.get_window_attributes(window)?;
+            let geometry = self.conn.get_geometry(window)?;
+            let (Ok(attributes), Ok(geometry)) = (attributes.reply(), geometry.reply()) else {
+                continue;
+            };
+            if attributes.map_state != MapState::VIEWABLE
+                || attributes.class != WindowClass::INPUT_OUTPUT
+            {
+                continue;
+            }
+            let covers = geometry.x <= own.x
+                && geometry.y <= own.y
+                && i32::from(geometry.x) + i32::from(geometry.width)
+                    >= i32::from(own.x) + i32::from(own.width)
+                && i32::from(geometry.y) + i32::from(geometry.height)
+                    >= i32::from(own.y) + i32::from(own.height);
+            if covers && self.is_fullscreen(window)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `window` or, for a window manager's frame, the client window
+    /// inside it has `_NET_WM_STATE_FULLSCREEN`.
+    fn is_fullscreen(&self, window: Window) -> anyhow::Result<bool> {
+        if self.has_fullscreen_state(window)? {
+            return Ok(true);
+        }
+        let Ok(tree) = self.conn.query_tree(window)?.reply() else {
+            return Ok(false);
+        };
+        for child in tree.children {
+            if self.has_fullscreen_state(child)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn has_fullscreen_state(&self, window: Window) -> anyhow::Result<bool> {
+        let Ok(state) = self
+            .conn
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                64,
+            )?
+            .reply()
+        else {
+            return Ok(false);
+        };
+        Ok(state
+            .value32()
+            .is_some_and(|mut atoms| atoms.any(|atom| atom == self.atoms._NET_WM_STATE_FULLSCREEN)))
+    }
+
+    /// The child of the root window that contains `window`. `None` once the
+    /// window is gone.
+    fn top_level(&self, mut window: Window) -> anyhow::Result<Option<Window>> {
+        loop {
+            let Ok(tree) = self.conn.query_tree(window)?.reply() else {
+                return Ok(None);
+            };
+            if tree.parent == self.root || tree.parent == x11rb::NONE {
+                return Ok(Some(window));
+            }
+            window = tree.parent;
+        }
+    }
+
+    /// Override-redirect windows go straight to the top; for managed ones
+    /// this asks the window manager, which may decline.
+    fn raise(&self) -> anyhow::Result<()> {
+        self.conn.configure_window(
+            self.overlay,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        )?;
+        Ok(())
+    }
+}
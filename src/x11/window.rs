@@ -0,0 +1,135 @@
+use x11rb::{
+    connection::Connection,
+    errors::ReplyError,
+    protocol::xproto::{AtomEnum, ConnectionExt as _, Window},
+};
+
+use crate::config::WindowSelector;
+
+x11rb::atom_manager! {
+    pub(crate) Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_WM_DESKTOP,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_SKIP_PAGER,
+        _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_STATE_STICKY,
+        UTF8_STRING,
+    }
+}
+
+/// First managed top-level window matching `selector`.
+pub(crate) fn find_window(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    selector: &WindowSelector,
+) -> Result<Option<Window>, ReplyError> {
+    for window in client_windows(conn, root, atoms)? {
+        if window_matches(conn, atoms, window, selector) {
+            return Ok(Some(window));
+        }
+    }
+    Ok(None)
+}
+
+/// `_NET_CLIENT_LIST` when the window manager publishes it, otherwise the
+/// root window's children.
+fn client_windows(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+) -> Result<Vec<Window>, ReplyError> {
+    let clients = conn
+        .get_property(
+            false,
+            root,
+            atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            0,
+            u32::MAX,
+        )?
+        .reply()?;
+    if let Some(windows) = clients.value32() {
+        let windows: Vec<Window> = windows.collect();
+        if !windows.is_empty() {
+            return Ok(windows);
+        }
+    }
+    Ok(conn.query_tree(root)?.reply()?.children)
+}
+
+/// Windows that disappear while being inspected simply do not match.
+pub(crate) fn window_matches(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    window: Window,
+    selector: &WindowSelector,
+) -> bool {
+    selector
+        .pid
+        .is_none_or(|pid| window_pid(conn, atoms, window) == Some(pid))
+        && selector.class.as_ref().is_none_or(|class| {
+            window_class(conn, window)
+                .is_some_and(|(instance, name)| instance == *class || name == *class)
+        })
+        && selector.title.as_ref().is_none_or(|title| {
+            window_title(conn, atoms, window).is_some_and(|name| title.0.is_match(&name))
+        })
+}
+
+pub(crate) fn window_pid(conn: &impl Connection, atoms: &Atoms, window: Window) -> Option<u32> {
+    let reply = conn
+        .get_property(false, window, atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    reply.value32()?.next()
+}
+
+/// `WM_CLASS` as (instance, class).
+pub(crate) fn window_class(conn: &impl Connection, window: Window) -> Option<(String, String)> {
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)
+        .ok()?
+        .reply()
+        .ok()?;
+    let mut parts = reply
+        .value
+        .split(|byte| *byte == 0)
+        .map(|part| String::from_utf8_lossy(part).into_owned());
+    Some((parts.next()?, parts.next().unwrap_or_default()))
+}
+
+/// `_NET_WM_NAME`, falling back to `WM_NAME`.
+pub(crate) fn window_title(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    window: Window,
+) -> Option<String> {
+    let utf8 = conn
+        .get_property(
+            false,
+            window,
+            atoms._NET_WM_NAME,
+            atoms.UTF8_STRING,
+            0,
+            4096,
+        )
+        .ok()?
+        .reply()
+        .ok()?;
+    if !utf8.value.is_empty() {
+        return Some(String::from_utf8_lossy(&utf8.value).into_owned());
+    }
+    let legacy = conn
+        .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 4096)
+        .ok()?
+        .reply()
+        .ok()?;
+    Some(String::from_utf8_lossy(&legacy.value).into_owned())
+}
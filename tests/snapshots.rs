@@ -0,0 +1,84 @@
+//! Renders the built-in widgets with the software rasterizer and compares
+//! them against the golden images in `tests/snapshots/`. Run with
+//! `RS_OVERLAY_UPDATE_SNAPSHOTS=1` to accept an intended change.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use rs_overlay::{
+    Config, FpsTracker, Overlay,
+    snapshot::{Tolerance, assert_matches_golden},
+};
+
+const SIZE: [u32; 2] = [320, 160];
+
+fn render(name: &str, config: &str, fps: &FpsTracker) {
+    let config = Config::parse(Path::new("snapshot.toml"), config).expect("valid config");
+    let snapshot = Overlay::from_config(&config).snapshot(fps, SIZE, 1.0);
+    assert_matches_golden(&snapshot, golden(name), Tolerance::default());
+}
+
+fn golden(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.png"))
+}
+
+/// Four seconds at 60 fps with a 50 ms hitch once a second.
+fn steady_frames() -> FpsTracker {
+    let mut tracker = FpsTracker::new();
+    let mut at = Instant::now();
+    for frame in 0..240 {
+        at += if frame % 60 == 59 {
+            Duration::from_millis(50)
+        } else {
+            Duration::from_micros(16_667)
+        };
+        tracker.record(at);
+    }
+    tracker
+}
+
+#[test]
+fn fps_without_frames() {
+    render(
+        "fps_without_frames",
+        "[[widget]]\ntype = \"fps\"\n",
+        &FpsTracker::new(),
+    );
+}
+
+#[test]
+fn fps_statistics() {
+    render(
+        "fps_statistics",
+        r#"
+        [[widget]]
+        type = "fps"
+        metrics = ["mean", "p50", "p99", "low_1"]
+        "#,
+        &steady_frames(),
+    );
+}
+
+#[test]
+fn graph() {
+    render("graph", "[[widget]]\ntype = \"graph\"\n", &steady_frames());
+}
+
+#[test]
+fn text() {
+    render(
+        "text",
+        r#"
+        [[widget]]
+        type = "text"
+        text = "Hello, overlay"
+        anchor = "center"
+        font_size = 24
+        "#,
+        &FpsTracker::new(),
+    );
+}